@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+pub const INITIAL_RATING: f64 = 1500.0;
+const K: f64 = 32.0;
+
+/// Elo ratings, extended to rounds with more than two players by treating every round as a
+/// match between each pair of its players, won by whoever survived longer.
+#[derive(Debug, Default)]
+pub struct Ratings {
+    ratings: BTreeMap<String, f64>,
+}
+
+impl Ratings {
+    pub fn get(&self, name: &str) -> f64 {
+        self.ratings.get(name).copied().unwrap_or(INITIAL_RATING)
+    }
+
+    /// Updates the ratings from one round, given as `(name, ticks survived)` for every player.
+    /// Players with the same name (i.e. the same strategy) don't play against each other.
+    ///
+    /// `K` is split between the opponents of each player, so a round moves a rating as much as
+    /// a single two-player match would, however many play in it.
+    pub fn update(&mut self, players: &[(&str, usize)]) {
+        let opponents = players.len().saturating_sub(1).max(1) as f64;
+        let mut deltas: BTreeMap<&str, f64> = BTreeMap::new();
+
+        for (i, &(a, survived_a)) in players.iter().enumerate() {
+            for &(b, survived_b) in &players[i + 1..] {
+                if a == b {
+                    continue;
+                }
+
+                let score = match survived_a.cmp(&survived_b) {
+                    std::cmp::Ordering::Greater => 1.0,
+                    std::cmp::Ordering::Equal => 0.5,
+                    std::cmp::Ordering::Less => 0.0,
+                };
+                let expected = 1.0 / (1.0 + 10f64.powf((self.get(b) - self.get(a)) / 400.0));
+                let delta = K / opponents * (score - expected);
+                *deltas.entry(a).or_default() += delta;
+                *deltas.entry(b).or_default() -= delta;
+            }
+        }
+
+        for (name, delta) in deltas {
+            *self.ratings.entry(name.to_string()).or_insert(INITIAL_RATING) += delta;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn total(ratings: &Ratings, names: &[&str]) -> f64 {
+        names.iter().map(|name| ratings.get(name)).sum()
+    }
+
+    #[test]
+    fn updates_are_zero_sum() {
+        let mut ratings = Ratings::default();
+        let names = ["a", "b", "c", "d"];
+        for survived in [[10, 3, 7, 7], [1, 20, 5, 2], [4, 4, 9, 30]] {
+            let players: Vec<_> = names.iter().copied().zip(survived).collect();
+            ratings.update(&players);
+            assert!((total(&ratings, &names) - 4.0 * INITIAL_RATING).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn ties_between_equals_change_nothing() {
+        let mut ratings = Ratings::default();
+        ratings.update(&[("a", 12), ("b", 12), ("c", 12)]);
+        for name in ["a", "b", "c"] {
+            assert_eq!(ratings.get(name), INITIAL_RATING);
+        }
+    }
+
+    #[test]
+    fn surviving_longer_gains_rating() {
+        let mut ratings = Ratings::default();
+        ratings.update(&[("a", 30), ("b", 20), ("c", 10)]);
+        assert!(ratings.get("a") > INITIAL_RATING);
+        assert_eq!(ratings.get("b"), INITIAL_RATING);
+        assert!(ratings.get("c") < INITIAL_RATING);
+    }
+
+    #[test]
+    fn k_is_split_between_opponents() {
+        // Between equals, beating everyone is worth half of K however many opponents there are
+        for players in [2, 3, 5] {
+            let mut ratings = Ratings::default();
+            let names: Vec<String> = (0..players).map(|i| format!("p{i}")).collect();
+            let round: Vec<_> = names.iter().enumerate().map(|(i, name)| (name.as_str(), players - i)).collect();
+            ratings.update(&round);
+            assert!((ratings.get("p0") - INITIAL_RATING - K / 2.0).abs() < 1e-9, "{players} players");
+        }
+    }
+
+    #[test]
+    fn the_same_strategy_doesnt_play_itself() {
+        let mut ratings = Ratings::default();
+        ratings.update(&[("a", 30), ("a", 10)]);
+        assert_eq!(ratings.get("a"), INITIAL_RATING);
+    }
+}
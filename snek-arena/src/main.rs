@@ -0,0 +1,296 @@
+mod elo;
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::{self, Child, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use clap::Parser;
+use snek::client;
+use snek::config::{Config, Verbosity};
+use snek::connection::Connection;
+use snek::strategy;
+use snek_protocol::{Command, Edges, HeadOn, Rules};
+use snek_server::{RoundResult, Server, ServerConfig};
+
+use crate::elo::Ratings;
+
+const PASSWORD: &str = "arena";
+
+/// Share of a tick bots may spend thinking, and after which they send a fallback move.
+const BUDGET_SHARE: f64 = 0.8;
+const FALLBACK_SHARE: f64 = 0.6;
+
+/// How long to wait for a round to end before assuming the bots got stuck.
+const ROUND_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Lets strategies play each other on a local server and ranks them.
+#[derive(Parser, Debug)]
+struct Args {
+    /// Strategies to enter, one bot each. Name a strategy twice to enter it twice.
+    #[arg(required = true)]
+    strategies: Vec<String>,
+
+    #[arg(long, default_value_t = 1000)]
+    rounds: usize,
+
+    /// Players per round. Defaults to every bot playing every round.
+    #[arg(long)]
+    players: Option<usize>,
+
+    #[arg(long, default_value_t = 32)]
+    width: usize,
+
+    #[arg(long, default_value_t = 32)]
+    height: usize,
+
+    #[arg(long, default_value_t = 1)]
+    seed: u64,
+
+    /// Ticks per second. Ticks end early once every bot has moved, so this mostly limits
+    /// how long the bots may think about a move.
+    #[arg(long, default_value_t = 20.0, value_parser = parse_tick_rate)]
+    tick_rate: f64,
+
+    /// Run every bot as a subprocess of this `snek` binary instead of in-process
+    #[arg(long)]
+    subprocess: Option<PathBuf>,
+
+    /// What happens at the edges of the map: wrap or walls
+    #[arg(long, default_value_t = Edges::Wrap)]
+    edges: Edges,
+
+    /// Leave the trails of dead players on the map
+    #[arg(long)]
+    keep_dead_trails: bool,
+
+    /// Who dies when players move into the same cell: all-die or lowest-id-wins
+    #[arg(long, default_value_t = HeadOn::AllDie)]
+    head_on: HeadOn,
+}
+
+#[derive(Debug, Default)]
+struct Record {
+    bots: usize,
+    rounds: usize,
+    wins: usize,
+    losses: usize,
+    ticks_survived: usize,
+}
+
+fn parse_tick_rate(arg: &str) -> Result<f64, String> {
+    let rate: f64 = arg.parse().map_err(|err| format!("{err}"))?;
+    // Only the tick rate is up to the argument, the other settings are checked in main
+    let config = ServerConfig { tick_rate: rate, ..ServerConfig::default() };
+    config.validate().map_err(|err| err.to_string())?;
+    Ok(rate)
+}
+
+fn main() {
+    let args = Args::parse();
+    for name in &args.strategies {
+        if strategy::by_name(name).is_none() {
+            eprintln!("Unknown strategy '{name}', available: {}", strategy::NAMES.join(", "));
+            process::exit(1);
+        }
+    }
+
+    let players = args.players.unwrap_or(args.strategies.len());
+    if players == 0 || players > args.strategies.len() {
+        eprintln!("Can't play rounds of {players} with {} bots", args.strategies.len());
+        process::exit(1);
+    }
+
+    let rules = Rules { edges: args.edges, clear_dead_trails: !args.keep_dead_trails, head_on: args.head_on };
+    let (results, inbox) = mpsc::channel();
+    let config = ServerConfig {
+        width: args.width,
+        height: args.height,
+        players,
+        tick_rate: args.tick_rate,
+        seed: args.seed,
+        fast_forward: true,
+        rules,
+        results: Some(results),
+        ..ServerConfig::default()
+    };
+    let server = Server::start(config, "127.0.0.1:0").unwrap_or_else(|err| {
+        eprintln!("Failed to start server: {err}");
+        process::exit(1);
+    });
+
+    // Bots have to answer within a tick, otherwise the server moves on without them
+    let tick = Duration::from_secs_f64(1.0 / args.tick_rate);
+    let bot_config = Config {
+        verbosity: Verbosity::Silent,
+        tick_budget: tick.mul_f64(BUDGET_SHARE),
+        fallback_after: tick.mul_f64(FALLBACK_SHARE),
+        // The arena keeps its own score
+        stats: None,
+        rules,
+        ..Config::default()
+    };
+    if let Err(err) = bot_config.check_timing() {
+        eprintln!("A tick rate of {} leaves the bots no time: {err}", args.tick_rate);
+        process::exit(1);
+    }
+
+    let mut strategy_of = BTreeMap::new();
+    let mut children = vec![];
+    for (i, name) in args.strategies.iter().enumerate() {
+        let username = format!("{name}-{i}");
+        strategy_of.insert(username.clone(), name.clone());
+        match &args.subprocess {
+            Some(binary) => children.push(spawn_subprocess(binary, server.local_addr(), &username, name, &bot_config)),
+            None => spawn_in_process(server.local_addr(), username, name.clone(), bot_config.clone()),
+        }
+    }
+
+    let mut records: BTreeMap<String, Record> = BTreeMap::new();
+    for name in &args.strategies {
+        records.entry(name.clone()).or_default().bots += 1;
+    }
+    let mut ratings = Ratings::default();
+
+    for round in 1..=args.rounds {
+        let result = match inbox.recv_timeout(ROUND_TIMEOUT) {
+            Ok(result) => result,
+            Err(_) => {
+                eprintln!("No round finished in {ROUND_TIMEOUT:?}, giving up after {} rounds", round - 1);
+                break;
+            }
+        };
+
+        book(&result, &strategy_of, &mut records, &mut ratings);
+        if round % 100 == 0 {
+            println!("{round} rounds played");
+        }
+    }
+
+    for mut child in children {
+        let _ = child.kill();
+    }
+    drop(server);
+
+    print_table(&records, &ratings);
+}
+
+fn spawn_in_process(addr: SocketAddr, username: String, strategy_name: String, config: Config) {
+    thread::spawn(move || {
+        let mut strategy = strategy::by_name(&strategy_name).expect("Strategy was checked");
+        let mut conn = match Connection::connect(addr) {
+            Ok(conn) => conn,
+            Err(err) => {
+                eprintln!("{username} couldn't connect to the arena: {err}");
+                return;
+            }
+        };
+        let join = Command::Join { username: username.clone(), password: PASSWORD.to_string() };
+        if let Err(err) = client::session_loop(&mut conn, &join, strategy.as_mut(), &config) {
+            eprintln!("{username} left the arena: {err}");
+        }
+    });
+}
+
+fn spawn_subprocess(binary: &PathBuf, addr: SocketAddr, username: &str, strategy_name: &str, config: &Config) -> Child {
+    let mut command = process::Command::new(binary);
+    command
+        .args(["--host", &addr.ip().to_string(), "--port", &addr.port().to_string()])
+        .args(["--username", username, "--password", PASSWORD, "--strategy", strategy_name])
+        .args(["--tick-budget-ms", &config.tick_budget.as_millis().to_string()])
+        .args(["--fallback-after-ms", &config.fallback_after.as_millis().to_string()])
+        .args(["--edges", config.rules.edges.as_str(), "--head-on", config.rules.head_on.as_str()])
+        .args(["--quiet", "--no-record", "--no-stats", "--max-reconnects", "1"])
+        .stdout(Stdio::null());
+    if !config.rules.clear_dead_trails {
+        command.arg("--keep-dead-trails");
+    }
+    command.spawn().unwrap_or_else(|err| {
+        eprintln!("Failed to start {}: {err}", binary.display());
+        process::exit(1);
+    })
+}
+
+fn book(
+    result: &RoundResult,
+    strategy_of: &BTreeMap<String, String>,
+    records: &mut BTreeMap<String, Record>,
+    ratings: &mut Ratings,
+) {
+    let mut players = vec![];
+    for (player_id, username) in result.usernames.iter().enumerate() {
+        let Some(name) = strategy_of.get(username) else { continue };
+        let survived = result.survived[player_id];
+        players.push((name.as_str(), survived));
+
+        let record = records.entry(name.clone()).or_default();
+        record.rounds += 1;
+        record.ticks_survived += survived;
+        if result.winner == Some(player_id) {
+            record.wins += 1;
+        } else {
+            record.losses += 1;
+        }
+    }
+
+    ratings.update(&players);
+}
+
+fn print_table(records: &BTreeMap<String, Record>, ratings: &Ratings) {
+    let mut rows: Vec<_> = records.iter().collect();
+    rows.sort_by(|(a, _), (b, _)| ratings.get(b).total_cmp(&ratings.get(a)));
+
+    println!();
+    println!("{:<16} {:>4} {:>7} {:>7} {:>7} {:>7} {:>10} {:>7}", "strategy", "bots", "rounds", "wins", "losses", "win %", "avg ticks", "elo");
+    for (name, record) in rows {
+        let rounds = record.rounds.max(1) as f64;
+        println!(
+            "{:<16} {:>4} {:>7} {:>7} {:>7} {:>7.1} {:>10.1} {:>7.0}",
+            name,
+            record.bots,
+            record.rounds,
+            record.wins,
+            record.losses,
+            100.0 * record.wins as f64 / rounds,
+            record.ticks_survived as f64 / rounds,
+            ratings.get(name),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn books_rounds_per_strategy() {
+        let strategy_of: BTreeMap<_, _> =
+            [("fill-0", "fill"), ("fill-1", "fill"), ("minimax-2", "minimax")].map(|(user, name)| (user.to_string(), name.to_string())).into();
+        let result = RoundResult {
+            usernames: ["minimax-2", "fill-1", "stranger", "fill-0"].map(String::from).to_vec(),
+            winner: Some(0),
+            survived: vec![40, 25, 30, 10],
+        };
+        let mut records = BTreeMap::new();
+        let mut ratings = Ratings::default();
+        book(&result, &strategy_of, &mut records, &mut ratings);
+
+        let minimax = &records["minimax"];
+        assert_eq!((minimax.rounds, minimax.wins, minimax.losses, minimax.ticks_survived), (1, 1, 0, 40));
+        let fill = &records["fill"];
+        assert_eq!((fill.rounds, fill.wins, fill.losses, fill.ticks_survived), (2, 0, 2, 35));
+        assert!(!records.contains_key("stranger"));
+        assert!(ratings.get("minimax") > ratings.get("fill"));
+    }
+
+    #[test]
+    fn tick_rates_must_be_positive() {
+        assert_eq!(parse_tick_rate("12.5"), Ok(12.5));
+        for rate in ["0", "-3", "NaN", "inf", "fast", "1e-320"] {
+            assert!(parse_tick_rate(rate).is_err(), "{rate}");
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Direction, ParseError};
+
+/// A packet sent from a client to the game server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Join { username: String, password: String },
+    Move(Direction),
+    Chat(String),
+}
+
+impl FromStr for Command {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Command, ParseError> {
+        let line = s.trim_end_matches(['\r', '\n']);
+        match line.split_once('|') {
+            Some(("join", rest)) => match rest.split_once('|') {
+                Some((username, password)) => Ok(Command::Join {
+                    username: username.to_string(),
+                    password: password.to_string(),
+                }),
+                None => Err(ParseError::new(line, "missing password")),
+            },
+
+            Some(("move", dir)) => dir
+                .parse()
+                .map(Command::Move)
+                .map_err(|_| ParseError::new(line, format!("invalid Move.direction '{dir}'"))),
+
+            Some(("chat", msg)) => Ok(Command::Chat(msg.to_string())),
+
+            _ => Err(ParseError::new(line, "unknown command")),
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Join { username, password } => write!(f, "join|{username}|{password}"),
+            Command::Move(dir) => write!(f, "move|{dir}"),
+            Command::Chat(msg) => write!(f, "chat|{msg}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(cmd: Command) {
+        let line = cmd.to_string();
+        assert_eq!(line.parse::<Command>(), Ok(cmd), "line: {line}");
+    }
+
+    #[test]
+    fn all_commands_round_trip() {
+        round_trip(Command::Join { username: "Snekisnek".to_string(), password: "hunter2".to_string() });
+        for dir in Direction::ALL {
+            round_trip(Command::Move(dir));
+        }
+        round_trip(Command::Chat("gg | wp".to_string()));
+    }
+
+    #[test]
+    fn parses_client_lines() {
+        assert_eq!("move|left\n".parse::<Command>(), Ok(Command::Move(Direction::Left)));
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        for line in ["", "move", "move|north", "join|bob", "chat", "dance|now"] {
+            assert!(line.parse::<Command>().is_err(), "accepted '{line}'");
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! The line protocol spoken by the [gpn-tron](https://github.com/freehuntx/gpn-tron) game server.
+//!
+//! Every packet is a single line of `|`-separated fields. [`Message`] covers everything the
+//! server sends to a client, [`Command`] everything a client sends to the server. Both can be
+//! parsed with [`str::parse`] and written back with their `Display` implementation, which
+//! produces the line without the trailing newline.
+//!
+//! [`Rules`] describe the variants of the game itself, which the protocol leaves unsaid.
+
+mod command;
+mod message;
+mod rules;
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+pub use command::Command;
+pub use message::Message;
+pub use rules::{Edges, HeadOn, Rules};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Up => "up",
+            Direction::Right => "right",
+            Direction::Down => "down",
+            Direction::Left => "left",
+        }
+    }
+
+    pub fn turn_right(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+
+    pub fn turn_left(self) -> Direction {
+        self.turn_right().opposite()
+    }
+
+    pub fn opposite(self) -> Direction {
+        self.turn_right().turn_right()
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Direction {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Direction, ParseError> {
+        match s {
+            "up" => Ok(Direction::Up),
+            "right" => Ok(Direction::Right),
+            "down" => Ok(Direction::Down),
+            "left" => Ok(Direction::Left),
+            _ => Err(ParseError::new(s, "unknown direction")),
+        }
+    }
+}
+
+/// A line that could not be turned into a [`Message`] or [`Command`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    line: String,
+    reason: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(line: &str, reason: impl Into<String>) -> ParseError {
+        ParseError {
+            line: line.to_string(),
+            reason: reason.into(),
+        }
+    }
+
+    /// The offending line, without its trailing newline.
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to parse '{}': {}", self.line, self.reason)
+    }
+}
+
+impl Error for ParseError {}
+
+pub(crate) fn parse_field<T: FromStr>(line: &str, field: &str, name: &str) -> Result<T, ParseError> {
+    field
+        .parse::<T>()
+        .map_err(|_| ParseError::new(line, format!("invalid {name} '{field}'")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn direction_round_trip() {
+        for dir in Direction::ALL {
+            assert_eq!(dir.to_string().parse::<Direction>(), Ok(dir));
+        }
+    }
+
+    #[test]
+    fn turning() {
+        for dir in Direction::ALL {
+            assert_eq!(dir.turn_left().turn_right(), dir);
+            assert_eq!(dir.opposite().opposite(), dir);
+            assert_ne!(dir.turn_right(), dir.turn_left());
+        }
+        assert_eq!(Direction::Up.turn_left(), Direction::Left);
+    }
+
+    #[test]
+    fn unknown_direction() {
+        assert!("north".parse::<Direction>().is_err());
+    }
+}
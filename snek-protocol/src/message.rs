@@ -0,0 +1,147 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{parse_field, ParseError};
+
+/// A packet sent from the game server to a client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Motd(String),
+    Error(String),
+    Game { width: usize, height: usize, player_id: usize },
+    Pos { player_id: usize, x: usize, y: usize },
+    Tick,
+    Die(Vec<usize>),
+    Message { player_id: usize, msg: String },
+    Win { wins: usize, losses: usize },
+    Lose { wins: usize, losses: usize },
+}
+
+impl FromStr for Message {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Message, ParseError> {
+        let line = s.trim_end_matches(['\r', '\n']);
+        let (tag, rest) = match line.split_once('|') {
+            Some((tag, rest)) => (tag, Some(rest)),
+            None => (line, None),
+        };
+
+        // Free-text fields come last, so everything after the fixed fields belongs to them,
+        // including any `|` the sender put into their text.
+        match (tag, rest) {
+            ("motd", Some(msg)) => Ok(Message::Motd(msg.to_string())),
+
+            ("error", Some(msg)) => Ok(Message::Error(msg.to_string())),
+
+            ("tick", None) => Ok(Message::Tick),
+
+            ("die", Some(players)) => {
+                let list = players
+                    .split('|')
+                    .map(|id| parse_field(line, id, "Die.player_id"))
+                    .collect::<Result<_, _>>()?;
+                Ok(Message::Die(list))
+            }
+
+            ("message", Some(rest)) => match rest.split_once('|') {
+                Some((player_id, msg)) => Ok(Message::Message {
+                    player_id: parse_field(line, player_id, "Message.player_id")?,
+                    msg: msg.to_string(),
+                }),
+                None => Err(ParseError::new(line, "missing message text")),
+            },
+
+            (_, Some(rest)) => {
+                let fields: Vec<_> = rest.split('|').collect();
+                match (tag, fields.as_slice()) {
+                    ("game", [width, height, player_id]) => Ok(Message::Game {
+                        width: parse_field(line, width, "Game.width")?,
+                        height: parse_field(line, height, "Game.height")?,
+                        player_id: parse_field(line, player_id, "Game.player_id")?,
+                    }),
+
+                    ("pos", [id, x, y]) => Ok(Message::Pos {
+                        player_id: parse_field(line, id, "Pos.player_id")?,
+                        x: parse_field(line, x, "Pos.x")?,
+                        y: parse_field(line, y, "Pos.y")?,
+                    }),
+
+                    ("win", [wins, losses]) => Ok(Message::Win {
+                        wins: parse_field(line, wins, "Win.wins")?,
+                        losses: parse_field(line, losses, "Win.losses")?,
+                    }),
+
+                    ("lose", [wins, losses]) => Ok(Message::Lose {
+                        wins: parse_field(line, wins, "Lose.wins")?,
+                        losses: parse_field(line, losses, "Lose.losses")?,
+                    }),
+
+                    _ => Err(ParseError::new(line, "unknown message")),
+                }
+            }
+
+            _ => Err(ParseError::new(line, "unknown message")),
+        }
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Message::Motd(msg) => write!(f, "motd|{msg}"),
+            Message::Error(msg) => write!(f, "error|{msg}"),
+            Message::Game { width, height, player_id } => write!(f, "game|{width}|{height}|{player_id}"),
+            Message::Pos { player_id, x, y } => write!(f, "pos|{player_id}|{x}|{y}"),
+            Message::Tick => write!(f, "tick"),
+            Message::Die(ids) => {
+                write!(f, "die")?;
+                for id in ids {
+                    write!(f, "|{id}")?;
+                }
+                Ok(())
+            }
+            Message::Message { player_id, msg } => write!(f, "message|{player_id}|{msg}"),
+            Message::Win { wins, losses } => write!(f, "win|{wins}|{losses}"),
+            Message::Lose { wins, losses } => write!(f, "lose|{wins}|{losses}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(msg: Message) {
+        let line = msg.to_string();
+        assert_eq!(line.parse::<Message>(), Ok(msg), "line: {line}");
+    }
+
+    #[test]
+    fn all_messages_round_trip() {
+        round_trip(Message::Motd("Welcome to gpn-tron!".to_string()));
+        round_trip(Message::Error("ERROR_INVALID_USERNAME".to_string()));
+        round_trip(Message::Game { width: 32, height: 24, player_id: 3 });
+        round_trip(Message::Pos { player_id: 1, x: 0, y: 17 });
+        round_trip(Message::Tick);
+        round_trip(Message::Die(vec![4]));
+        round_trip(Message::Die(vec![0, 2, 5]));
+        round_trip(Message::Message { player_id: 2, msg: "hi | there".to_string() });
+        round_trip(Message::Win { wins: 10, losses: 3 });
+        round_trip(Message::Lose { wins: 0, losses: 1 });
+    }
+
+    #[test]
+    fn parses_server_lines() {
+        assert_eq!("pos|2|5|7\n".parse::<Message>(), Ok(Message::Pos { player_id: 2, x: 5, y: 7 }));
+        assert_eq!("tick\r\n".parse::<Message>(), Ok(Message::Tick));
+        assert_eq!("motd|a|b".parse::<Message>(), Ok(Message::Motd("a|b".to_string())));
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        for line in ["", "tick|1", "pos|1|2", "pos|a|2|3", "die", "die|1|x", "message|1", "win|1", "player|1|bob"] {
+            assert!(line.parse::<Message>().is_err(), "accepted '{line}'");
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Direction, ParseError};
+
+/// How a round is played. The protocol doesn't say, so bots have to be told the rules of the
+/// server they play on. Defaults to the rules of gpn-tron.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct Rules {
+    pub edges: Edges,
+    /// Whether the trails of players that died disappear from the map.
+    pub clear_dead_trails: bool,
+    pub head_on: HeadOn,
+}
+
+impl Rules {
+    /// gpn-tron: wrap around the edges, dead trails vanish and head-on collisions kill all.
+    pub const GPN_TRON: Rules = Rules { edges: Edges::Wrap, clear_dead_trails: true, head_on: HeadOn::AllDie };
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules::GPN_TRON
+    }
+}
+
+/// What happens at the edge of the map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Edges {
+    /// The map is a torus, leaving it on one side means entering it on the other.
+    Wrap,
+    /// Leaving the map is as deadly as running into a trail.
+    Walls,
+}
+
+impl Edges {
+    /// The cell one step from `(x, y)` in `dir` on a `width` x `height` map, `None` if that
+    /// is off the map.
+    pub fn step(self, width: usize, height: usize, x: usize, y: usize, dir: Direction) -> Option<(usize, usize)> {
+        match self {
+            Edges::Wrap => Some(match dir {
+                Direction::Up => (x, (y + height - 1) % height),
+                Direction::Down => (x, (y + 1) % height),
+                Direction::Right => ((x + 1) % width, y),
+                Direction::Left => ((x + width - 1) % width, y),
+            }),
+            Edges::Walls => match dir {
+                Direction::Up => Some((x, y.checked_sub(1)?)),
+                Direction::Down => Some((x, y + 1)).filter(|&(_, y)| y < height),
+                Direction::Right => Some((x + 1, y)).filter(|&(x, _)| x < width),
+                Direction::Left => Some((x.checked_sub(1)?, y)),
+            },
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Edges::Wrap => "wrap",
+            Edges::Walls => "walls",
+        }
+    }
+}
+
+/// What happens when several players move into the same cell at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum HeadOn {
+    /// Everyone involved dies.
+    AllDie,
+    /// The player with the lowest id gets the cell and the others run into them, as on servers
+    /// that move players one after the other.
+    LowestIdWins,
+}
+
+impl HeadOn {
+    /// Which of the players `ids` moving into the same cell survives.
+    pub fn survivor(self, ids: impl IntoIterator<Item = usize>) -> Option<usize> {
+        match self {
+            HeadOn::AllDie => None,
+            HeadOn::LowestIdWins => ids.into_iter().min(),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HeadOn::AllDie => "all-die",
+            HeadOn::LowestIdWins => "lowest-id-wins",
+        }
+    }
+}
+
+impl fmt::Display for Edges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Edges {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Edges, ParseError> {
+        match s {
+            "wrap" => Ok(Edges::Wrap),
+            "walls" => Ok(Edges::Walls),
+            _ => Err(ParseError::new(s, "unknown edges, expected wrap or walls")),
+        }
+    }
+}
+
+impl fmt::Display for HeadOn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HeadOn {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<HeadOn, ParseError> {
+        match s {
+            "all-die" => Ok(HeadOn::AllDie),
+            "lowest-id-wins" => Ok(HeadOn::LowestIdWins),
+            _ => Err(ParseError::new(s, "unknown head-on rule, expected all-die or lowest-id-wins")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walls_end_the_map() {
+        for (edges, expected) in [(Edges::Wrap, Some((3, 0))), (Edges::Walls, None)] {
+            assert_eq!(edges.step(4, 3, 0, 0, Direction::Left), expected);
+            assert_eq!(edges.step(4, 3, 1, 1, Direction::Down), Some((1, 2)));
+        }
+        assert_eq!(Edges::Walls.step(4, 3, 1, 2, Direction::Down), None);
+        assert_eq!(Edges::Walls.step(4, 3, 3, 1, Direction::Right), None);
+        assert_eq!(Edges::Walls.step(4, 3, 1, 0, Direction::Up), None);
+    }
+
+    #[test]
+    fn rule_names_round_trip() {
+        for edges in [Edges::Wrap, Edges::Walls] {
+            assert_eq!(edges.to_string().parse::<Edges>(), Ok(edges));
+        }
+        for head_on in [HeadOn::AllDie, HeadOn::LowestIdWins] {
+            assert_eq!(head_on.to_string().parse::<HeadOn>(), Ok(head_on));
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use rand::Rng;
+use snek_protocol::{Direction, Rules};
+
+use crate::ConfigError;
+
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub x: usize,
+    pub y: usize,
+    pub direction: Direction,
+    pub alive: bool,
+    /// Set when the player left the game, so they die on the next step.
+    forfeit: bool,
+}
+
+/// The rules of a single round, without any networking. Player ids are indices into
+/// [`Game::players`].
+#[derive(Debug, Clone)]
+pub struct Game {
+    pub width: usize,
+    pub height: usize,
+    pub rules: Rules,
+    cells: Vec<Option<usize>>,
+    players: Vec<Player>,
+}
+
+impl Game {
+    /// Places `player_count` players on distinct random cells.
+    pub fn new(width: usize, height: usize, player_count: usize, rng: &mut impl Rng) -> Result<Game, ConfigError> {
+        if player_count > width * height {
+            return Err(ConfigError::MapTooSmall { width, height, players: player_count });
+        }
+
+        let mut positions: Vec<(usize, usize)> = Vec::with_capacity(player_count);
+        while positions.len() < player_count {
+            let pos = (rng.gen_range(0..width), rng.gen_range(0..height));
+            if !positions.contains(&pos) {
+                positions.push(pos);
+            }
+        }
+
+        Ok(Game::with_positions(width, height, &positions))
+    }
+
+    pub fn with_positions(width: usize, height: usize, positions: &[(usize, usize)]) -> Game {
+        let mut game = Game {
+            width,
+            height,
+            rules: Rules::default(),
+            cells: vec![None; width * height],
+            players: Vec::with_capacity(positions.len()),
+        };
+
+        for (id, &(x, y)) in positions.iter().enumerate() {
+            game.players.push(Player { x, y, direction: Direction::Up, alive: true, forfeit: false });
+            let offset = game.offset(x, y);
+            game.cells[offset] = Some(id);
+        }
+
+        game
+    }
+
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+
+    pub fn alive(&self) -> impl Iterator<Item = usize> + '_ {
+        self.players.iter().enumerate().filter(|(_, p)| p.alive).map(|(id, _)| id)
+    }
+
+    pub fn owner(&self, x: usize, y: usize) -> Option<usize> {
+        self.cells[self.offset(x, y)]
+    }
+
+    pub fn set_direction(&mut self, id: usize, dir: Direction) {
+        self.players[id].direction = dir;
+    }
+
+    pub fn forfeit(&mut self, id: usize) {
+        self.players[id].forfeit = true;
+    }
+
+    fn offset(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    /// The cell one step from `(x, y)` in `dir`, `None` if that's off the map. The same as
+    /// `GameRound::next_offset` in the bot.
+    pub fn next_pos(&self, x: usize, y: usize, dir: Direction) -> Option<(usize, usize)> {
+        self.rules.edges.step(self.width, self.height, x, y, dir)
+    }
+
+    /// Moves every living player one cell and returns the ids of those that died doing so.
+    /// A player dies when moving off the map or into any trail. Who dies when moving into the
+    /// same cell as another player, and whether trails of dead players are removed from the
+    /// map, is up to the [`Rules`].
+    pub fn step(&mut self) -> Vec<usize> {
+        let targets: Vec<Option<(usize, usize)>> = self
+            .players
+            .iter()
+            .map(|p| if p.alive { self.next_pos(p.x, p.y, p.direction) } else { None })
+            .collect();
+
+        let mut died = vec![];
+        for (id, target) in targets.iter().enumerate().filter(|&(id, _)| self.players[id].alive) {
+            // Ran off the map
+            let Some((x, y)) = *target else {
+                died.push(id);
+                continue;
+            };
+
+            let forfeit = self.players[id].forfeit;
+            let hits_trail = self.owner(x, y).is_some();
+            let contenders: Vec<_> = targets.iter().enumerate().filter(|&(_, t)| *t == Some((x, y))).map(|(other, _)| other).collect();
+            let head_on = contenders.len() > 1 && self.rules.head_on.survivor(contenders) != Some(id);
+
+            if forfeit || hits_trail || head_on {
+                died.push(id);
+            }
+        }
+
+        for (id, target) in targets.into_iter().enumerate() {
+            let Some((x, y)) = target else { continue };
+            if died.contains(&id) {
+                continue;
+            }
+
+            let offset = self.offset(x, y);
+            self.cells[offset] = Some(id);
+            let player = &mut self.players[id];
+            player.x = x;
+            player.y = y;
+        }
+
+        for &id in &died {
+            self.players[id].alive = false;
+        }
+        if self.rules.clear_dead_trails {
+            for cell in self.cells.iter_mut() {
+                if matches!(cell, Some(id) if died.contains(id)) {
+                    *cell = None;
+                }
+            }
+        }
+
+        died
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use snek_protocol::{Edges, HeadOn};
+
+    use super::*;
+
+    #[test]
+    fn wraps_around_the_edges() {
+        let game = Game::with_positions(4, 3, &[]);
+        assert_eq!(game.next_pos(0, 0, Direction::Up), Some((0, 2)));
+        assert_eq!(game.next_pos(0, 2, Direction::Down), Some((0, 0)));
+        assert_eq!(game.next_pos(0, 1, Direction::Left), Some((3, 1)));
+        assert_eq!(game.next_pos(3, 1, Direction::Right), Some((0, 1)));
+    }
+
+    #[test]
+    fn same_seed_same_spawns() {
+        let a = Game::new(20, 20, 5, &mut StdRng::seed_from_u64(42)).unwrap();
+        let b = Game::new(20, 20, 5, &mut StdRng::seed_from_u64(42)).unwrap();
+        let spawns = |g: &Game| g.players().iter().map(|p| (p.x, p.y)).collect::<Vec<_>>();
+        assert_eq!(spawns(&a), spawns(&b));
+    }
+
+    #[test]
+    fn running_into_a_trail_kills() {
+        let mut game = Game::with_positions(5, 5, &[(1, 1), (2, 2)]);
+        game.set_direction(1, Direction::Left);
+        assert_eq!(game.step(), vec![]);
+        assert_eq!(game.owner(1, 2), Some(1));
+
+        game.set_direction(1, Direction::Up);
+        assert_eq!(game.step(), vec![1]);
+        assert!(!game.players()[1].alive);
+        assert_eq!(game.owner(2, 2), None, "dead player's trail is removed");
+        assert_eq!(game.alive().collect::<Vec<_>>(), vec![0]);
+    }
+
+    #[test]
+    fn head_on_kills_both() {
+        let mut game = Game::with_positions(5, 5, &[(0, 0), (2, 0)]);
+        game.set_direction(0, Direction::Right);
+        game.set_direction(1, Direction::Left);
+        assert_eq!(game.step(), vec![0, 1]);
+    }
+
+    #[test]
+    fn walls_and_lasting_trails() {
+        let mut game = Game::with_positions(5, 5, &[(0, 0), (2, 2)]);
+        game.rules = Rules { edges: Edges::Walls, clear_dead_trails: false, ..Rules::default() };
+        game.set_direction(0, Direction::Left);
+        assert_eq!(game.step(), vec![0]);
+        assert_eq!(game.owner(0, 0), Some(0), "dead player's trail stays");
+        assert_eq!(game.players()[1].y, 1);
+    }
+
+    #[test]
+    fn head_on_may_go_to_the_lowest_id() {
+        let mut game = Game::with_positions(5, 5, &[(0, 0), (2, 0)]);
+        game.rules.head_on = HeadOn::LowestIdWins;
+        game.set_direction(0, Direction::Right);
+        game.set_direction(1, Direction::Left);
+        assert_eq!(game.step(), vec![1]);
+        assert_eq!(game.owner(1, 0), Some(0));
+    }
+}
@@ -0,0 +1,211 @@
+//! A local stand-in for the [gpn-tron](https://github.com/freehuntx/gpn-tron) game server.
+//!
+//! It speaks the same line protocol, so bots can be run against each other without the
+//! event's server, either from the `snek-server` binary or in-process via [`Server::start`].
+
+pub mod game;
+mod lobby;
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, BufReader};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use snek_protocol::{Command, Rules};
+
+pub use game::Game;
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub width: usize,
+    pub height: usize,
+    /// How many joined players it takes to start a round. Every round has exactly this many players.
+    pub players: usize,
+    /// Ticks per second.
+    pub tick_rate: f64,
+    /// Seeds the spawn positions, so the same seed and the same moves give the same games.
+    pub seed: u64,
+    pub motd: String,
+    /// Don't wait for the tick to be over once every living player has sent a move,
+    /// and start a new round as soon as there are enough players. For bots playing each
+    /// other as fast as they can, the tick rate then only matters for bots that don't answer.
+    pub fast_forward: bool,
+    pub rules: Rules,
+    /// Gets the outcome of every round.
+    pub results: Option<mpsc::Sender<RoundResult>>,
+}
+
+/// How a round ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundResult {
+    /// Indexed by player id.
+    pub usernames: Vec<String>,
+    /// Player id of the winner, if there was one.
+    pub winner: Option<usize>,
+    /// Number of ticks every player survived, indexed by player id.
+    pub survived: Vec<usize>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            width: 32,
+            height: 32,
+            players: 2,
+            tick_rate: 5.0,
+            seed: 0,
+            motd: "Welcome to snek-server!".to_string(),
+            fast_forward: false,
+            rules: Rules::default(),
+            results: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn tick_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.tick_rate)
+    }
+
+    /// Checks that rounds can be played with these settings.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.players == 0 {
+            return Err(ConfigError::NoPlayers);
+        }
+        if !self.tick_rate.is_finite() || self.tick_rate <= 0.0 || Duration::try_from_secs_f64(1.0 / self.tick_rate).is_err() {
+            return Err(ConfigError::TickRate(self.tick_rate));
+        }
+        if self.players > self.width * self.height {
+            return Err(ConfigError::MapTooSmall { width: self.width, height: self.height, players: self.players });
+        }
+        Ok(())
+    }
+}
+
+/// Settings the server can't play with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    NoPlayers,
+    TickRate(f64),
+    MapTooSmall { width: usize, height: usize, players: usize },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::NoPlayers => write!(f, "A round needs at least one player"),
+            ConfigError::TickRate(rate) => write!(f, "Tick rate must be a positive number of ticks per second, not {rate}"),
+            ConfigError::MapTooSmall { width, height, players } => write!(f, "A {width}x{height} map is too small for {players} players"),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+pub(crate) type ConnectionId = usize;
+
+pub(crate) enum Event {
+    Connected(ConnectionId, TcpStream),
+    Command(ConnectionId, Command),
+    Invalid(ConnectionId),
+    Disconnected(ConnectionId),
+}
+
+/// A running server. Dropping it shuts the server down.
+pub struct Server {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    listener: Option<JoinHandle<()>>,
+    game: Option<JoinHandle<()>>,
+}
+
+impl Server {
+    /// Binds to `addr` and starts accepting players in background threads. Bind to port 0
+    /// to let the OS pick a free port, and ask [`Server::local_addr`] for it. Fails with
+    /// [`io::ErrorKind::InvalidInput`] if the config doesn't [validate](ServerConfig::validate).
+    pub fn start(config: ServerConfig, addr: impl ToSocketAddrs) -> io::Result<Server> {
+        config.validate().map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let (events, inbox) = mpsc::channel();
+
+        let listener = {
+            let stop = stop.clone();
+            thread::spawn(move || accept_loop(listener, events, stop))
+        };
+
+        let game = {
+            let stop = stop.clone();
+            thread::spawn(move || lobby::Lobby::new(config).run(inbox, stop))
+        };
+
+        Ok(Server {
+            addr,
+            stop,
+            listener: Some(listener),
+            game: Some(game),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Blocks the calling thread for as long as the server runs, i.e. until the process exits.
+    pub fn wait(mut self) {
+        if let Some(game) = self.game.take() {
+            let _ = game.join();
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Wake up the accept loop so it notices that it should stop
+        let _ = TcpStream::connect(self.addr);
+        for handle in [self.listener.take(), self.game.take()].into_iter().flatten() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn accept_loop(listener: TcpListener, events: mpsc::Sender<Event>, stop: Arc<AtomicBool>) {
+    for (id, stream) in listener.incoming().enumerate() {
+        if stop.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let Ok(stream) = stream else { continue };
+        let _ = stream.set_nodelay(true);
+        let Ok(reader) = stream.try_clone() else { continue };
+        if events.send(Event::Connected(id, stream)).is_err() {
+            return;
+        }
+
+        let events = events.clone();
+        thread::spawn(move || read_loop(id, reader, events));
+    }
+}
+
+fn read_loop(id: ConnectionId, stream: TcpStream, events: mpsc::Sender<Event>) {
+    let reader = BufReader::new(stream);
+    for line in reader.lines() {
+        let Ok(line) = line else { break };
+        let event = match line.parse::<Command>() {
+            Ok(cmd) => Event::Command(id, cmd),
+            Err(_) => Event::Invalid(id),
+        };
+        if events.send(event).is_err() {
+            return;
+        }
+    }
+    let _ = events.send(Event::Disconnected(id));
+}
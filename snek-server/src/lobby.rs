@@ -0,0 +1,316 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Write;
+use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use snek_protocol::{Command, Message};
+
+use crate::game::Game;
+use crate::{ConnectionId, Event, RoundResult, ServerConfig};
+
+const MAX_USERNAME_LENGTH: usize = 32;
+
+struct Client {
+    stream: TcpStream,
+    username: Option<String>,
+    outbox: String,
+    kick: bool,
+}
+
+struct Account {
+    password: String,
+    wins: usize,
+    losses: usize,
+}
+
+struct Round {
+    game: Game,
+    /// The connection of every player, indexed by player id.
+    players: Vec<ConnectionId>,
+    usernames: Vec<String>,
+    ticks: usize,
+    survived: Vec<usize>,
+    /// Who sent a move since the last tick.
+    moved: Vec<bool>,
+}
+
+/// Owns all connections and runs the rounds. Lives on its own thread and is fed
+/// by the reader threads through [`Event`]s.
+pub(crate) struct Lobby {
+    config: ServerConfig,
+    rng: StdRng,
+    clients: BTreeMap<ConnectionId, Client>,
+    /// Joined players waiting for the next round, in the order they will get to play.
+    queue: VecDeque<ConnectionId>,
+    accounts: HashMap<String, Account>,
+    round: Option<Round>,
+}
+
+impl Lobby {
+    pub fn new(config: ServerConfig) -> Lobby {
+        Lobby {
+            rng: StdRng::seed_from_u64(config.seed),
+            config,
+            clients: BTreeMap::new(),
+            queue: VecDeque::new(),
+            accounts: HashMap::new(),
+            round: None,
+        }
+    }
+
+    pub fn run(mut self, inbox: Receiver<Event>, stop: Arc<AtomicBool>) {
+        let interval = self.config.tick_interval();
+        let mut next_tick = Instant::now() + interval;
+
+        while !stop.load(Ordering::SeqCst) {
+            match inbox.recv_timeout(next_tick.saturating_duration_since(Instant::now())) {
+                Ok(event) => self.handle(event),
+                Err(RecvTimeoutError::Timeout) => {
+                    self.tick();
+                    next_tick += interval;
+                    // Don't try to catch up if we fell behind, just keep the pace from here on
+                    next_tick = next_tick.max(Instant::now());
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            while self.config.fast_forward && self.ready_to_tick() {
+                self.tick();
+                next_tick = Instant::now() + interval;
+            }
+            self.flush();
+        }
+
+        for client in self.clients.values() {
+            let _ = client.stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Connected(id, stream) => {
+                self.clients.insert(id, Client { stream, username: None, outbox: String::new(), kick: false });
+                self.send(id, &Message::Motd(self.config.motd.clone()));
+            }
+
+            Event::Command(id, Command::Join { username, password }) => self.join(id, username, password),
+
+            Event::Command(id, Command::Move(dir)) => {
+                if let Some((player_id, round)) = self.player_in_round(id) {
+                    if round.game.players()[player_id].alive {
+                        round.game.set_direction(player_id, dir);
+                        round.moved[player_id] = true;
+                    }
+                }
+            }
+
+            Event::Command(id, Command::Chat(msg)) => {
+                if let Some((player_id, _)) = self.player_in_round(id) {
+                    self.broadcast(&Message::Message { player_id, msg });
+                }
+            }
+
+            Event::Invalid(id) => {
+                self.send(id, &Message::Error("ERROR_INVALID_PACKET".to_string()));
+            }
+
+            Event::Disconnected(id) => {
+                self.clients.remove(&id);
+                self.queue.retain(|&queued| queued != id);
+                if let Some((player_id, round)) = self.player_in_round(id) {
+                    round.game.forfeit(player_id);
+                }
+            }
+        }
+    }
+
+    fn join(&mut self, id: ConnectionId, username: String, password: String) {
+        let error = if self.clients.get(&id).is_some_and(|c| c.username.is_some()) {
+            Some("ERROR_ALREADY_JOINED")
+        } else if username.is_empty() || username.len() > MAX_USERNAME_LENGTH {
+            Some("ERROR_INVALID_USERNAME")
+        } else if self.clients.values().any(|c| c.username.as_ref() == Some(&username)) {
+            Some("ERROR_ALREADY_CONNECTED")
+        } else if self.accounts.get(&username).is_some_and(|a| a.password != password) {
+            Some("ERROR_WRONG_PASSWORD")
+        } else {
+            None
+        };
+
+        if let Some(error) = error {
+            self.send(id, &Message::Error(error.to_string()));
+            if let Some(client) = self.clients.get_mut(&id) {
+                client.kick = true;
+            }
+            return;
+        }
+
+        self.accounts
+            .entry(username.clone())
+            .or_insert(Account { password, wins: 0, losses: 0 });
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.username = Some(username);
+            self.queue.push_back(id);
+        }
+    }
+
+    fn player_in_round(&mut self, id: ConnectionId) -> Option<(usize, &mut Round)> {
+        let round = self.round.as_mut()?;
+        let player_id = round.players.iter().position(|&p| p == id)?;
+        Some((player_id, round))
+    }
+
+    /// Whether there is nothing left to wait for before the next tick.
+    fn ready_to_tick(&self) -> bool {
+        match &self.round {
+            Some(round) => round.game.alive().all(|player_id| round.moved[player_id]),
+            None => self.queue.len() >= self.config.players,
+        }
+    }
+
+    fn tick(&mut self) {
+        if self.round.is_some() {
+            self.step_round();
+        } else {
+            self.start_round();
+        }
+    }
+
+    fn start_round(&mut self) {
+        if self.queue.len() < self.config.players {
+            return;
+        }
+
+        let players: Vec<_> = self.queue.drain(..self.config.players).collect();
+        let usernames = players
+            .iter()
+            .map(|id| self.clients.get(id).and_then(|c| c.username.clone()).unwrap_or_default())
+            .collect();
+        let mut game = Game::new(self.config.width, self.config.height, players.len(), &mut self.rng)
+            .expect("The map fits the players, the server checked when it started");
+        game.rules = self.config.rules;
+
+        for (player_id, &id) in players.iter().enumerate() {
+            self.send(id, &Message::Game { width: game.width, height: game.height, player_id });
+        }
+        let count = players.len();
+        self.round = Some(Round {
+            game,
+            players,
+            usernames,
+            ticks: 0,
+            survived: vec![0; count],
+            moved: vec![false; count],
+        });
+        self.broadcast_positions();
+        self.broadcast(&Message::Tick);
+    }
+
+    fn step_round(&mut self) {
+        let Some(round) = self.round.as_mut() else { return };
+        let died = round.game.step();
+        let alive: Vec<_> = round.game.alive().collect();
+        let player_count = round.players.len();
+
+        round.ticks += 1;
+        round.moved.fill(false);
+        for &player_id in &alive {
+            round.survived[player_id] = round.ticks;
+        }
+
+        if !died.is_empty() {
+            self.broadcast(&Message::Die(died.clone()));
+            for player_id in died {
+                self.finish(player_id, false);
+            }
+        }
+
+        if alive.is_empty() || (player_count > 1 && alive.len() == 1) {
+            let winner = alive.first().copied();
+            if let Some(winner) = winner {
+                self.finish(winner, true);
+            }
+            self.end_round(winner);
+        } else {
+            self.broadcast_positions();
+            self.broadcast(&Message::Tick);
+        }
+    }
+
+    /// Books the result of a player and tells them about it, if they are still around.
+    fn finish(&mut self, player_id: usize, won: bool) {
+        let Some(round) = self.round.as_ref() else { return };
+        let id = round.players[player_id];
+        let Some(account) = self.accounts.get_mut(&round.usernames[player_id]) else { return };
+
+        let msg = if won {
+            account.wins += 1;
+            Message::Win { wins: account.wins, losses: account.losses }
+        } else {
+            account.losses += 1;
+            Message::Lose { wins: account.wins, losses: account.losses }
+        };
+        self.send(id, &msg);
+    }
+
+    fn end_round(&mut self, winner: Option<usize>) {
+        let Some(round) = self.round.take() else { return };
+        if let Some(results) = &self.config.results {
+            let _ = results.send(RoundResult { usernames: round.usernames, winner, survived: round.survived });
+        }
+        for id in round.players {
+            if self.clients.contains_key(&id) {
+                self.queue.push_back(id);
+            }
+        }
+    }
+
+    fn broadcast_positions(&mut self) {
+        let Some(round) = self.round.as_ref() else { return };
+        let positions: Vec<_> = round
+            .game
+            .alive()
+            .map(|player_id| {
+                let player = &round.game.players()[player_id];
+                Message::Pos { player_id, x: player.x, y: player.y }
+            })
+            .collect();
+
+        for msg in positions {
+            self.broadcast(&msg);
+        }
+    }
+
+    /// Sends `msg` to everyone taking part in the current round.
+    fn broadcast(&mut self, msg: &Message) {
+        let Some(round) = self.round.as_ref() else { return };
+        for id in round.players.clone() {
+            self.send(id, msg);
+        }
+    }
+
+    fn send(&mut self, id: ConnectionId, msg: &Message) {
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.outbox.push_str(&msg.to_string());
+            client.outbox.push('\n');
+        }
+    }
+
+    fn flush(&mut self) {
+        for client in self.clients.values_mut() {
+            if !client.outbox.is_empty() {
+                // A failed write means the client is gone, which its reader thread will tell us about
+                let _ = client.stream.write_all(client.outbox.as_bytes());
+                client.outbox.clear();
+            }
+            if client.kick {
+                let _ = client.stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+}
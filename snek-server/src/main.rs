@@ -0,0 +1,77 @@
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::Parser;
+use snek_protocol::{Edges, HeadOn, Rules};
+use snek_server::{Server, ServerConfig};
+
+/// Runs a local gpn-tron compatible game server.
+#[derive(Parser, Debug)]
+struct Args {
+    #[arg(long, default_value = "127.0.0.1:4000")]
+    listen: String,
+
+    #[arg(long, default_value_t = 32)]
+    width: usize,
+
+    #[arg(long, default_value_t = 32)]
+    height: usize,
+
+    /// Number of players in every round
+    #[arg(long, default_value_t = 2)]
+    players: usize,
+
+    /// Ticks per second
+    #[arg(long, default_value_t = 5.0)]
+    tick_rate: f64,
+
+    /// Seed for the spawn positions. Picked from the clock if not given.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Move on to the next tick as soon as every living player has sent a move
+    #[arg(long)]
+    fast_forward: bool,
+
+    /// What happens at the edges of the map: wrap or walls
+    #[arg(long, default_value_t = Edges::Wrap)]
+    edges: Edges,
+
+    /// Leave the trails of dead players on the map
+    #[arg(long)]
+    keep_dead_trails: bool,
+
+    /// Who dies when players move into the same cell: all-die or lowest-id-wins
+    #[arg(long, default_value_t = HeadOn::AllDie)]
+    head_on: HeadOn,
+}
+
+fn main() {
+    let args = Args::parse();
+    let seed = args.seed.unwrap_or_else(|| {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+    });
+
+    let config = ServerConfig {
+        width: args.width,
+        height: args.height,
+        players: args.players,
+        tick_rate: args.tick_rate,
+        seed,
+        fast_forward: args.fast_forward,
+        rules: Rules { edges: args.edges, clear_dead_trails: !args.keep_dead_trails, head_on: args.head_on },
+        ..ServerConfig::default()
+    };
+
+    if let Err(err) = config.validate() {
+        eprintln!("{err}");
+        process::exit(2);
+    }
+
+    let server = Server::start(config, &args.listen).unwrap_or_else(|err| {
+        eprintln!("Failed to start server on {}: {err}", args.listen);
+        process::exit(1);
+    });
+    println!("Listening on {} with seed {seed}", server.local_addr());
+    server.wait();
+}
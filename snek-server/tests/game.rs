@@ -0,0 +1,119 @@
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use snek_protocol::{Command, Message};
+use snek_server::{ConfigError, Server, ServerConfig};
+
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Client {
+    fn connect(addr: SocketAddr) -> Client {
+        let writer = TcpStream::connect(addr).unwrap();
+        let reader = BufReader::new(writer.try_clone().unwrap());
+        Client { reader, writer }
+    }
+
+    fn join(addr: SocketAddr, username: &str) -> Client {
+        let mut client = Client::connect(addr);
+        assert!(matches!(client.recv(), Message::Motd(_)));
+        client.send(Command::Join { username: username.to_string(), password: "secret".to_string() });
+        client
+    }
+
+    fn send(&mut self, cmd: Command) {
+        writeln!(self.writer, "{cmd}").unwrap();
+    }
+
+    fn recv(&mut self) -> Message {
+        let mut line = String::new();
+        assert!(self.reader.read_line(&mut line).unwrap() > 0, "connection closed");
+        line.parse().unwrap()
+    }
+
+    /// Reads until the next message that isn't a position update.
+    fn recv_skipping_positions(&mut self) -> Message {
+        loop {
+            match self.recv() {
+                Message::Pos { .. } => continue,
+                msg => return msg,
+            }
+        }
+    }
+}
+
+fn start_server() -> Server {
+    let config = ServerConfig { width: 16, height: 16, players: 2, tick_rate: 50.0, seed: 7, ..ServerConfig::default() };
+    Server::start(config, "127.0.0.1:0").unwrap()
+}
+
+#[test]
+fn plays_a_round() {
+    let server = start_server();
+    let mut alice = Client::join(server.local_addr(), "alice");
+    let mut bob = Client::join(server.local_addr(), "bob");
+
+    for (expected_id, client) in [(0, &mut alice), (1, &mut bob)] {
+        assert_eq!(client.recv(), Message::Game { width: 16, height: 16, player_id: expected_id });
+        let mut ids = vec![];
+        for _ in 0..2 {
+            match client.recv() {
+                Message::Pos { player_id, .. } => ids.push(player_id),
+                msg => panic!("expected a position, got {msg:?}"),
+            }
+        }
+        ids.sort();
+        assert_eq!(ids, vec![0, 1]);
+        assert_eq!(client.recv(), Message::Tick);
+    }
+
+    bob.send(Command::Chat("bye".to_string()));
+    drop(bob);
+
+    loop {
+        match alice.recv_skipping_positions() {
+            Message::Tick | Message::Message { .. } => continue,
+            msg => {
+                assert_eq!(msg, Message::Die(vec![1]));
+                break;
+            }
+        }
+    }
+    assert_eq!(alice.recv_skipping_positions(), Message::Win { wins: 1, losses: 0 });
+}
+
+#[test]
+fn rejects_duplicate_usernames() {
+    let server = start_server();
+    let _alice = Client::join(server.local_addr(), "alice");
+    let mut impostor = Client::join(server.local_addr(), "alice");
+    assert_eq!(impostor.recv(), Message::Error("ERROR_ALREADY_CONNECTED".to_string()));
+}
+
+#[test]
+fn rejects_invalid_packets() {
+    let server = start_server();
+    let mut client = Client::connect(server.local_addr());
+    assert!(matches!(client.recv(), Message::Motd(_)));
+    writeln!(client.writer, "dance|now").unwrap();
+    assert_eq!(client.recv(), Message::Error("ERROR_INVALID_PACKET".to_string()));
+}
+
+#[test]
+fn refuses_to_start_without_a_playable_config() {
+    let configs = [
+        (ServerConfig { players: 0, ..ServerConfig::default() }, ConfigError::NoPlayers),
+        (ServerConfig { tick_rate: 0.0, ..ServerConfig::default() }, ConfigError::TickRate(0.0)),
+        (ServerConfig { tick_rate: f64::INFINITY, ..ServerConfig::default() }, ConfigError::TickRate(f64::INFINITY)),
+        (ServerConfig { width: 0, ..ServerConfig::default() }, ConfigError::MapTooSmall { width: 0, height: 32, players: 2 }),
+        (ServerConfig { width: 1, height: 1, ..ServerConfig::default() }, ConfigError::MapTooSmall { width: 1, height: 1, players: 2 }),
+    ];
+    for (config, expected) in configs {
+        assert_eq!(config.validate(), Err(expected));
+        let err = Server::start(config, "127.0.0.1:0").err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+    assert!(ServerConfig { tick_rate: f64::NAN, ..ServerConfig::default() }.validate().is_err());
+}
@@ -0,0 +1,29 @@
+//! Stamps the bot with the commit it was built from, so the stats can tell versions of a
+//! strategy apart. Builds outside of a git checkout just get the package version.
+
+use std::env;
+use std::process::Command;
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    let out = String::from_utf8(output.stdout).ok()?;
+    Some(out.trim().to_string()).filter(|out| output.status.success() && !out.is_empty())
+}
+
+fn main() {
+    let package = env::var("CARGO_PKG_VERSION").unwrap_or_default();
+    let version = match git(&["describe", "--always", "--dirty"]) {
+        Some(commit) => format!("{package}+{commit}"),
+        None => package,
+    };
+    println!("cargo:rustc-env=SNEK_VERSION={version}");
+
+    // A new commit, or changes making the tree dirty or clean again
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]) {
+        for path in ["HEAD", "index", "refs"] {
+            println!("cargo:rerun-if-changed={git_dir}/{path}");
+        }
+    }
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=build.rs");
+}
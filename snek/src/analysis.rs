@@ -0,0 +1,237 @@
+//! Evaluations of the board beyond "is the next cell free". These work on whole bitsets of the
+//! [`Board`](crate::board::Board) at a time rather than cell by cell.
+
+use snek_protocol::Direction;
+
+use crate::board::CellSet;
+use crate::GameRound;
+
+/// The free cells reachable from `start`, counting `start` itself. Empty if `start` is taken.
+pub fn region(round: &GameRound, start: usize) -> CellSet {
+    let board = &round.board;
+    let mut region = board.no_cells();
+    if !round.is_free(start) {
+        return region;
+    }
+
+    region.insert(start);
+    flood(region, &board.free())
+}
+
+/// `cells` plus everything connected to them through cells `within`.
+pub fn flood(mut cells: CellSet, within: &CellSet) -> CellSet {
+    loop {
+        let mut grown = cells.spread();
+        grown.and_assign(within);
+        grown.or_assign(&cells);
+        if grown == cells {
+            return cells;
+        }
+        cells = grown;
+    }
+}
+
+/// Number of free cells reachable from `start`, counting `start` itself. Zero if `start` is taken.
+pub fn reachable_area(round: &GameRound, start: usize) -> usize {
+    region(round, start).count()
+}
+
+/// Like [`reachable_area`], but stops counting at `limit`, which is a lot cheaper when the
+/// area is large.
+pub fn reachable_area_up_to(round: &GameRound, start: usize, limit: usize) -> usize {
+    let board = &round.board;
+    if !round.is_free(start) {
+        return 0;
+    }
+
+    let free = board.free();
+    let mut region = board.no_cells();
+    region.insert(start);
+    let mut area = 1;
+    while area < limit {
+        let mut grown = region.spread();
+        grown.and_assign(&free);
+        let grown_area = grown.count();
+        if grown_area == area {
+            break;
+        }
+        region = grown;
+        area = grown_area;
+    }
+
+    area.min(limit)
+}
+
+/// The area we could still reach after moving in each direction that isn't blocked.
+pub fn area_per_direction(round: &GameRound) -> Vec<(Direction, usize)> {
+    let head = round.offset(round.x, round.y);
+    Direction::ALL
+        .into_iter()
+        .filter_map(|dir| Some((dir, round.free_neighbour(head, dir)?)))
+        .map(|(dir, next)| (dir, reachable_area(round, next)))
+        .collect()
+}
+
+/// Which player gets to each free cell first, if everyone raced for it from their current head.
+#[derive(Debug, Clone)]
+pub struct Voronoi {
+    /// The cells each player reaches before anyone else, indexed by player id. Taken cells,
+    /// cells nobody can reach, and cells two or more players reach at the same time are in none.
+    pub regions: Vec<CellSet>,
+    /// Number of cells owned, indexed by player id.
+    pub area: Vec<usize>,
+}
+
+impl Voronoi {
+    pub fn area_of(&self, player_id: usize) -> usize {
+        self.area.get(player_id).copied().unwrap_or(0)
+    }
+
+    /// The player who gets to `offset` first, if anyone.
+    pub fn owner(&self, offset: usize) -> Option<usize> {
+        self.regions.iter().position(|region| region.contains(offset))
+    }
+}
+
+/// Partitions the free cells between the players starting from `heads`, given as
+/// `(player_id, offset)`. The heads themselves are taken cells and not counted.
+///
+/// All players spread out one step at a time. Cells reached by more than one player in the
+/// same step are contested. Those spread out further as well, but nothing behind them can be
+/// claimed through them.
+pub fn voronoi(round: &GameRound, heads: &[(usize, usize)]) -> Voronoi {
+    let board = &round.board;
+    let player_count = heads.iter().map(|&(id, _)| id + 1).max().unwrap_or(0);
+    let mut regions = vec![board.no_cells(); player_count];
+
+    let mut frontiers: Vec<(usize, CellSet)> = heads
+        .iter()
+        .map(|&(id, offset)| {
+            let mut frontier = board.no_cells();
+            frontier.insert(offset);
+            (id, frontier)
+        })
+        .collect();
+    let mut contested = board.no_cells();
+    let mut unclaimed = board.free();
+
+    loop {
+        let grow = |cells: &CellSet| {
+            let mut grown = cells.spread();
+            grown.and_assign(&unclaimed);
+            grown
+        };
+        let grown: Vec<CellSet> = frontiers.iter().map(|(_, frontier)| grow(frontier)).collect();
+        let mut reached = board.no_cells();
+        let mut reached_twice = grow(&contested);
+        for cells in &grown {
+            reached_twice.or_assign(&reached.and(cells));
+            reached.or_assign(cells);
+        }
+        reached.or_assign(&reached_twice);
+        if reached.is_empty() {
+            break;
+        }
+
+        for ((id, frontier), cells) in frontiers.iter_mut().zip(grown) {
+            *frontier = cells.and_not(&reached_twice);
+            regions[*id].or_assign(frontier);
+        }
+        unclaimed = unclaimed.and_not(&reached);
+        contested = reached_twice;
+    }
+
+    let area = regions.iter().map(CellSet::count).collect();
+    Voronoi { regions, area }
+}
+
+/// Our territory after moving in `dir`, while everyone else stays where they are.
+/// `None` if the move is blocked.
+pub fn territory_after_move(round: &GameRound, dir: Direction) -> Option<usize> {
+    let head = round.offset(round.x, round.y);
+    let next = round.free_neighbour(head, dir)?;
+
+    // Pretend we already moved, so our new head is taken like everyone else's
+    let mut moved = round.clone();
+    moved.board.take(next, round.player_id);
+
+    let heads: Vec<_> = round
+        .head_offsets()
+        .map(|(id, offset)| if id == round.player_id { (id, next) } else { (id, offset) })
+        .collect();
+
+    Some(voronoi(&moved, &heads).area_of(round.player_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use snek_protocol::{Edges, Rules};
+
+    use super::*;
+
+    fn walled(width: usize, height: usize) -> GameRound {
+        GameRound::with_rules(0, width, height, Rules { edges: Edges::Walls, ..Rules::default() })
+    }
+
+    #[test]
+    fn nothing_is_reachable_from_a_taken_cell() {
+        let mut round = GameRound::new(0, 4, 4);
+        round.move_player(0, 1, 1);
+        let head = round.offset(1, 1);
+
+        assert!(region(&round, head).is_empty());
+        assert_eq!(reachable_area(&round, head), 0);
+        assert_eq!(reachable_area_up_to(&round, head, 10), 0);
+    }
+
+    #[test]
+    fn regions_wrap_around_unless_there_are_walls() {
+        // A trail down the second column cuts the map in two, unless it wraps
+        for mut round in [GameRound::new(0, 4, 4), walled(4, 4)] {
+            for y in 0..4 {
+                round.move_player(1, 1, y);
+            }
+            let expected = if round.rules.edges == Edges::Wrap { 12 } else { 4 };
+            assert_eq!(reachable_area(&round, round.offset(0, 0)), expected);
+        }
+    }
+
+    #[test]
+    fn counting_stops_at_the_limit() {
+        let round = GameRound::new(0, 8, 8);
+        let start = round.offset(3, 3);
+
+        assert_eq!(reachable_area_up_to(&round, start, 5), 5);
+        assert_eq!(reachable_area_up_to(&round, start, 64), 64);
+        assert_eq!(reachable_area_up_to(&round, start, 100), 64);
+    }
+
+    #[test]
+    fn cells_as_close_to_two_heads_are_contested() {
+        let mut round = walled(5, 3);
+        round.move_player(0, 0, 1);
+        round.move_player(1, 4, 1);
+        let heads: Vec<_> = round.head_offsets().collect();
+        let voronoi = voronoi(&round, &heads);
+
+        // The middle column is as far from both heads, everything else goes to the closer one
+        assert_eq!(voronoi.area, vec![5, 5]);
+        for y in 0..3 {
+            assert_eq!(voronoi.owner(round.offset(2, y)), None);
+            assert_eq!(voronoi.owner(round.offset(1, y)), Some(0));
+            assert_eq!(voronoi.owner(round.offset(3, y)), Some(1));
+        }
+        assert_eq!(voronoi.owner(round.offset(0, 1)), None);
+    }
+
+    #[test]
+    fn territory_after_moving() {
+        let mut round = walled(5, 3);
+        round.move_player(0, 0, 1);
+        round.move_player(1, 4, 1);
+
+        assert_eq!(territory_after_move(&round, Direction::Left), None);
+        // Moving towards the opponent claims the middle column, but not the one next to them
+        assert_eq!(territory_after_move(&round, Direction::Right), Some(7));
+    }
+}
@@ -0,0 +1,488 @@
+//! The map as bitsets: one for all taken cells and one per player for their trail.
+//!
+//! Cloning a [`Board`] only bumps reference counts, the bitsets are copied the first time a
+//! clone is changed. Searches that would rather not copy anything can take a
+//! [`Board::checkpoint`] and [`Board::undo`] their moves afterwards.
+
+use std::sync::Arc;
+
+use snek_protocol::{Direction, Edges};
+
+/// A set of cells of a map, one bit per cell. Every row starts on a new word, so moving the
+/// whole set by a row is copying words and moving it by a column is shifting each row, which
+/// for maps up to 64 cells wide is a single word.
+///
+/// Cells are addressed by offset (`y * width + x`), like everywhere else.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellSet {
+    words: Vec<u64>,
+    width: usize,
+    height: usize,
+    /// Words per row.
+    stride: usize,
+    /// Whether moving cells over an edge brings them in on the other side, or drops them.
+    edges: Edges,
+}
+
+impl CellSet {
+    /// No cells of a `width` x `height` map.
+    pub fn new(width: usize, height: usize, edges: Edges) -> CellSet {
+        let stride = width.div_ceil(64);
+        CellSet { words: vec![0; stride * height], width, height, stride, edges }
+    }
+
+    /// No cells of the same map.
+    fn empty(&self) -> CellSet {
+        CellSet { words: vec![0; self.words.len()], ..*self }
+    }
+
+    fn wraps(&self) -> bool {
+        self.edges == Edges::Wrap
+    }
+
+    fn index(&self, offset: usize) -> (usize, u32) {
+        let (x, y) = (offset % self.width, offset / self.width);
+        (y * self.stride + x / 64, (x % 64) as u32)
+    }
+
+    /// The bits of the last word of a row that are cells of the map.
+    fn tail_mask(&self) -> u64 {
+        match self.width % 64 {
+            0 => !0,
+            used => (1 << used) - 1,
+        }
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        let (word, bit) = self.index(offset);
+        self.words[word] & (1 << bit) != 0
+    }
+
+    pub fn insert(&mut self, offset: usize) {
+        let (word, bit) = self.index(offset);
+        self.words[word] |= 1 << bit;
+    }
+
+    pub fn remove(&mut self, offset: usize) {
+        let (word, bit) = self.index(offset);
+        self.words[word] &= !(1 << bit);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    /// Number of cells in the set.
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Offsets of the cells in the set, in ascending order.
+    pub fn offsets(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(move |(i, &word)| {
+            let row_start = (i / self.stride) * self.width + (i % self.stride) * 64;
+            let mut rest = word;
+            std::iter::from_fn(move || {
+                if rest == 0 {
+                    return None;
+                }
+                let bit = rest.trailing_zeros() as usize;
+                rest &= rest - 1;
+                Some(row_start + bit)
+            })
+        })
+    }
+
+    pub fn and(&self, other: &CellSet) -> CellSet {
+        self.zip(other, |a, b| a & b)
+    }
+
+    /// The cells in this set but not in `other`.
+    pub fn and_not(&self, other: &CellSet) -> CellSet {
+        self.zip(other, |a, b| a & !b)
+    }
+
+    /// Every cell of the map not in this set.
+    pub fn not(&self) -> CellSet {
+        let mut result = self.clone();
+        let tail = self.tail_mask();
+        for (i, word) in result.words.iter_mut().enumerate() {
+            *word = !*word;
+            if i % self.stride == self.stride - 1 {
+                *word &= tail;
+            }
+        }
+        result
+    }
+
+    pub fn or_assign(&mut self, other: &CellSet) {
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a |= b;
+        }
+    }
+
+    pub fn and_assign(&mut self, other: &CellSet) {
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a &= b;
+        }
+    }
+
+    pub fn and_not_assign(&mut self, other: &CellSet) {
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a &= !b;
+        }
+    }
+
+    /// Every cell moved one step in `dir`. Cells moved over an edge wrap around, or are
+    /// gone if the map has walls.
+    pub fn shift(&self, dir: Direction) -> CellSet {
+        let mut result = self.empty();
+        let (stride, height) = (self.stride, self.height);
+        for y in 0..height {
+            let row = &self.words[y * stride..(y + 1) * stride];
+            let to = match dir {
+                Direction::Up if y == 0 && !self.wraps() => continue,
+                Direction::Down if y == height - 1 && !self.wraps() => continue,
+                Direction::Up => (y + height - 1) % height,
+                Direction::Down => (y + 1) % height,
+                Direction::Right | Direction::Left => y,
+            };
+            let out = &mut result.words[to * stride..(to + 1) * stride];
+            match dir {
+                Direction::Up | Direction::Down => out.copy_from_slice(row),
+                Direction::Right => self.shift_row_right(row, out),
+                Direction::Left => self.shift_row_left(row, out),
+            }
+        }
+        result
+    }
+
+    /// Moves the cells of one row a column towards larger x.
+    fn shift_row_right(&self, row: &[u64], out: &mut [u64]) {
+        let last = self.width - 1;
+        let wrapped = if self.wraps() { row[last / 64] >> (last % 64) & 1 } else { 0 };
+        let mut carry = wrapped;
+        for (word, out) in row.iter().zip(out.iter_mut()) {
+            *out = word << 1 | carry;
+            carry = word >> 63;
+        }
+        out[row.len() - 1] &= self.tail_mask();
+    }
+
+    /// Moves the cells of one row a column towards smaller x.
+    fn shift_row_left(&self, row: &[u64], out: &mut [u64]) {
+        let last = self.width - 1;
+        for i in 0..row.len() {
+            let next = row.get(i + 1).map_or(0, |word| word << 63);
+            out[i] = row[i] >> 1 | next;
+        }
+        if self.wraps() {
+            out[last / 64] |= (row[0] & 1) << (last % 64);
+        }
+    }
+
+    /// The set plus every cell next to it.
+    pub fn spread(&self) -> CellSet {
+        let (width, height, stride) = (self.width, self.height, self.stride);
+        let rows = &self.words;
+        let mut result = self.empty();
+        // The rows below and above row `y`, nothing past a wall
+        let below = |y: usize| (y + 1 < height || self.wraps()).then(|| (y + 1) % height);
+        let above = |y: usize| (y > 0 || self.wraps()).then(|| (y + height - 1) % height);
+        if stride == 1 {
+            // A word per row, so no carrying between words
+            let tail = self.tail_mask();
+            let wrapped = |row: u64| if self.wraps() { row >> (width - 1) | (row & 1) << (width - 1) } else { 0 };
+            for y in 0..height {
+                let row = rows[y];
+                let sideways = (row << 1 | row >> 1 | wrapped(row)) & tail;
+                let vertical = below(y).map_or(0, |b| rows[b]) | above(y).map_or(0, |a| rows[a]);
+                result.words[y] = row | sideways | vertical;
+            }
+            return result;
+        }
+
+        let (mut right, mut left) = (vec![0; stride], vec![0; stride]);
+        let none = vec![0; stride];
+        for y in 0..height {
+            let row = |y: usize| &rows[y * stride..(y + 1) * stride];
+            self.shift_row_right(row(y), &mut right);
+            self.shift_row_left(row(y), &mut left);
+            let (below, above) = (below(y).map_or(&none[..], row), above(y).map_or(&none[..], row));
+            for i in 0..stride {
+                result.words[y * stride + i] = row(y)[i] | right[i] | left[i] | below[i] | above[i];
+            }
+        }
+        result
+    }
+
+    fn zip(&self, other: &CellSet, op: impl Fn(u64, u64) -> u64) -> CellSet {
+        let words = self.words.iter().zip(&other.words).map(|(&a, &b)| op(a, b)).collect();
+        CellSet { words, ..*self }
+    }
+}
+
+/// A change to the board that can be undone.
+#[derive(Debug)]
+enum Change {
+    /// Whether the cell was taken before, and by the player themselves, so undoing the change
+    /// doesn't free cells that were taken already.
+    Took { offset: usize, player: usize, was_occupied: bool, was_owned: bool },
+    Cleared { player: usize, trail: Arc<CellSet> },
+}
+
+/// Which cells of a map are taken, and by whom.
+#[derive(Debug)]
+pub struct Board {
+    width: usize,
+    height: usize,
+    edges: Edges,
+    occupied: Arc<CellSet>,
+    /// The trail of every player, indexed by player id.
+    layers: Vec<Arc<CellSet>>,
+    /// Changes since the first checkpoint. `None` while nobody wants to undo anything.
+    journal: Option<Vec<Change>>,
+}
+
+impl Clone for Board {
+    /// Cheap, the copy shares its bitsets with the original until either changes them.
+    /// The copy starts without checkpoints.
+    fn clone(&self) -> Board {
+        Board {
+            width: self.width,
+            height: self.height,
+            edges: self.edges,
+            occupied: Arc::clone(&self.occupied),
+            layers: self.layers.clone(),
+            journal: None,
+        }
+    }
+}
+
+impl Board {
+    pub fn new(width: usize, height: usize, edges: Edges) -> Board {
+        Board {
+            width,
+            height,
+            edges,
+            occupied: Arc::new(CellSet::new(width, height, edges)),
+            layers: vec![],
+            journal: None,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Number of cells on the map.
+    pub fn cells(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// An empty set of cells of this map.
+    pub fn no_cells(&self) -> CellSet {
+        CellSet::new(self.width, self.height, self.edges)
+    }
+
+    pub fn offset(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        (offset % self.width, offset / self.width)
+    }
+
+    /// The cell one step from `(x, y)` in `dir`. `None` if that's over a wall, on maps
+    /// without walls leaving the map on one side means entering it on the other.
+    pub fn next_offset(&self, x: usize, y: usize, dir: Direction) -> Option<usize> {
+        let (x, y) = self.edges.step(self.width, self.height, x, y, dir)?;
+        Some(self.offset(x, y))
+    }
+
+    pub fn neighbour(&self, offset: usize, dir: Direction) -> Option<usize> {
+        let (x, y) = self.position(offset);
+        self.next_offset(x, y, dir)
+    }
+
+    pub fn is_free(&self, offset: usize) -> bool {
+        !self.occupied.contains(offset)
+    }
+
+    /// The player whose trail is on `offset`, if any.
+    pub fn owner(&self, offset: usize) -> Option<usize> {
+        if self.is_free(offset) {
+            return None;
+        }
+        self.layers.iter().position(|layer| layer.contains(offset))
+    }
+
+    /// All taken cells.
+    pub fn occupied(&self) -> &CellSet {
+        &self.occupied
+    }
+
+    /// All free cells.
+    pub fn free(&self) -> CellSet {
+        self.occupied.not()
+    }
+
+    /// The trail of player `id`, if they ever had one.
+    pub fn trail(&self, id: usize) -> Option<&CellSet> {
+        self.layers.get(id).map(|layer| &**layer)
+    }
+
+    /// Player `id` takes the cell at `offset`.
+    pub fn take(&mut self, offset: usize, id: usize) {
+        if self.layers.len() <= id {
+            let empty = Arc::new(self.no_cells());
+            self.layers.resize(id + 1, empty);
+        }
+        if let Some(journal) = self.journal.as_mut() {
+            journal.push(Change::Took {
+                offset,
+                player: id,
+                was_occupied: self.occupied.contains(offset),
+                was_owned: self.layers[id].contains(offset),
+            });
+        }
+
+        Arc::make_mut(&mut self.occupied).insert(offset);
+        Arc::make_mut(&mut self.layers[id]).insert(offset);
+    }
+
+    /// Removes the trail of player `id` from the map.
+    pub fn clear_player(&mut self, id: usize) {
+        let empty = self.no_cells();
+        let Some(layer) = self.layers.get_mut(id) else { return };
+        if layer.is_empty() {
+            return;
+        }
+
+        let trail = std::mem::replace(layer, Arc::new(empty));
+        Arc::make_mut(&mut self.occupied).and_not_assign(&trail);
+        if let Some(journal) = self.journal.as_mut() {
+            journal.push(Change::Cleared { player: id, trail });
+        }
+    }
+
+    /// Starts remembering changes, so they can be undone back to this point.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        Checkpoint(self.journal.get_or_insert_with(Vec::new).len())
+    }
+
+    /// Undoes every change since `checkpoint`. Checkpoints taken after it can't be used anymore.
+    pub fn undo(&mut self, checkpoint: Checkpoint) {
+        let Some(mut journal) = self.journal.take() else { return };
+        while journal.len() > checkpoint.0 {
+            match journal.pop() {
+                Some(Change::Took { offset, player, was_occupied, was_owned }) => {
+                    if !was_occupied {
+                        Arc::make_mut(&mut self.occupied).remove(offset);
+                    }
+                    if !was_owned {
+                        Arc::make_mut(&mut self.layers[player]).remove(offset);
+                    }
+                }
+
+                Some(Change::Cleared { player, trail }) => {
+                    Arc::make_mut(&mut self.occupied).or_assign(&trail);
+                    self.layers[player] = trail;
+                }
+
+                None => break,
+            }
+        }
+        self.journal = Some(journal);
+    }
+}
+
+/// A point to [`Board::undo`] back to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint(usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shifts_wrap_around_the_edges_or_stop_at_walls() {
+        // Rows fitting a word, rows spanning words, rows filling their words exactly, one column
+        let sizes = [(13, 11), (70, 3), (128, 2), (1, 4)];
+        for ((width, height), edges) in sizes.into_iter().flat_map(|size| [(size, Edges::Wrap), (size, Edges::Walls)]) {
+            let board = Board::new(width, height, edges);
+            let cells = [(0, 0), (width - 1, height - 1), (width / 2, 0), (0, height - 1), (63.min(width - 1), 1)];
+            for (x, y) in cells {
+                let mut cell = board.no_cells();
+                cell.insert(board.offset(x, y));
+
+                let mut neighbours = vec![board.offset(x, y)];
+                for dir in Direction::ALL {
+                    let expected: Vec<_> = board.next_offset(x, y, dir).into_iter().collect();
+                    let shifted: Vec<_> = cell.shift(dir).offsets().collect();
+                    assert_eq!(shifted, expected, "{width}x{height} {edges} ({x}, {y}) {dir}");
+                    neighbours.extend(expected);
+                }
+
+                neighbours.sort();
+                neighbours.dedup();
+                let spread: Vec<_> = cell.spread().offsets().collect();
+                assert_eq!(spread, neighbours, "{width}x{height} {edges} ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn clones_share_until_changed() {
+        let mut board = Board::new(8, 8, Edges::Wrap);
+        board.take(3, 0);
+        let mut copy = board.clone();
+        copy.take(4, 1);
+
+        assert!(!board.is_free(3) && board.is_free(4));
+        assert_eq!(copy.owner(3), Some(0));
+        assert_eq!(copy.owner(4), Some(1));
+    }
+
+    #[test]
+    fn undo_restores_trails() {
+        let mut board = Board::new(6, 6, Edges::Wrap);
+        board.take(1, 0);
+        board.take(2, 1);
+
+        let checkpoint = board.checkpoint();
+        board.take(3, 0);
+        board.clear_player(1);
+        board.take(2, 0);
+        assert_eq!(board.owner(2), Some(0));
+
+        board.undo(checkpoint);
+        assert_eq!(board.owner(1), Some(0));
+        assert_eq!(board.owner(2), Some(1));
+        assert!(board.is_free(3));
+        assert_eq!(board.occupied().count(), 2);
+    }
+
+    #[test]
+    fn undo_keeps_cells_taken_before() {
+        let mut board = Board::new(6, 6, Edges::Wrap);
+        board.take(1, 0);
+        board.take(2, 1);
+
+        // Both move onto cells that were already taken, as in a head-on collision
+        let checkpoint = board.checkpoint();
+        board.take(1, 0);
+        board.take(1, 1);
+        board.take(2, 1);
+
+        board.undo(checkpoint);
+        assert_eq!(board.owner(1), Some(0));
+        assert_eq!(board.owner(2), Some(1));
+        assert!(!board.trail(1).unwrap().contains(1));
+        assert_eq!(board.occupied().count(), 2);
+    }
+}
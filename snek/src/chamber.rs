@@ -0,0 +1,232 @@
+//! Splits free space into chambers: parts of the map connected to each other only through
+//! articulation points, cells that cut a region in two once taken. Entering a chamber through
+//! an articulation point means giving up on everything on the other side of it, so the space
+//! we can actually fill is less than what a flood fill counts.
+
+use snek_protocol::Direction;
+
+use crate::analysis;
+use crate::board::CellSet;
+use crate::GameRound;
+
+/// The cells of `region` that disconnect it when taken. `region` is expected to be connected,
+/// like the ones [`analysis::region`] returns.
+pub fn articulation_points(round: &GameRound, region: &CellSet) -> CellSet {
+    let board = &round.board;
+    let mut points = board.no_cells();
+    // Depth-first discovery order, starting at 1 so that 0 means not visited yet
+    let mut order = vec![0u32; board.cells()];
+    // The earliest discovered cell reachable through the subtree of each cell and one back edge
+    let mut low = vec![0u32; board.cells()];
+    let mut counter = 0;
+
+    for root in region.offsets() {
+        if order[root] != 0 {
+            continue;
+        }
+        counter += 1;
+        order[root] = counter;
+        low[root] = counter;
+
+        // (cell, parent, index of the next direction to look at)
+        let mut stack = vec![(root, root, 0)];
+        let mut root_children = 0;
+        while let Some(top) = stack.last_mut() {
+            let (cell, parent, next) = *top;
+            if next < Direction::ALL.len() {
+                top.2 += 1;
+                let Some(neighbour) = board.neighbour(cell, Direction::ALL[next]) else { continue };
+                if !region.contains(neighbour) || neighbour == parent {
+                    continue;
+                }
+                if order[neighbour] == 0 {
+                    counter += 1;
+                    order[neighbour] = counter;
+                    low[neighbour] = counter;
+                    stack.push((neighbour, cell, 0));
+                } else {
+                    low[cell] = low[cell].min(order[neighbour]);
+                }
+                continue;
+            }
+
+            stack.pop();
+            if parent == cell {
+                continue;
+            }
+            low[parent] = low[parent].min(low[cell]);
+            if parent == root {
+                root_children += 1;
+            } else if low[cell] >= order[parent] {
+                points.insert(parent);
+            }
+        }
+
+        if root_children > 1 {
+            points.insert(root);
+        }
+    }
+
+    points
+}
+
+/// A region split at its articulation points.
+#[derive(Debug, Clone)]
+pub struct Chambers {
+    /// The articulation points, which belong to no chamber.
+    pub cuts: CellSet,
+    /// The connected parts left once the articulation points are taken.
+    pub chambers: Vec<CellSet>,
+}
+
+/// Splits `region` into chambers.
+pub fn chambers(round: &GameRound, region: &CellSet) -> Chambers {
+    let cuts = articulation_points(round, region);
+    let mut rest = region.and_not(&cuts);
+    let mut chambers = vec![];
+    loop {
+        let Some(start) = rest.offsets().next() else { break };
+        let mut seed = round.board.no_cells();
+        seed.insert(start);
+        let chamber = analysis::flood(seed, &rest);
+        rest.and_not_assign(&chamber);
+        chambers.push(chamber);
+    }
+    Chambers { cuts, chambers }
+}
+
+/// How many cells we could fill starting at the free cell `start`, counting `start` itself.
+/// Every chamber along the way counts fully, but at each articulation point we have to pick
+/// one side to continue on. Zero if `start` is taken.
+///
+/// The count is an upper bound, not every chamber can be filled completely.
+pub fn fillable_area(round: &GameRound, start: usize) -> usize {
+    let region = analysis::region(round, start);
+    if region.is_empty() {
+        return 0;
+    }
+    let Chambers { cuts, chambers } = chambers(round, &region);
+
+    // Chambers and articulation points are the nodes of a tree, each weighs its cell count
+    let board = &round.board;
+    let mut node_of = vec![usize::MAX; board.cells()];
+    let mut weight = vec![];
+    for chamber in &chambers {
+        for offset in chamber.offsets() {
+            node_of[offset] = weight.len();
+        }
+        weight.push(chamber.count());
+    }
+    for offset in cuts.offsets() {
+        node_of[offset] = weight.len();
+        weight.push(1);
+    }
+
+    let mut neighbours = vec![vec![]; weight.len()];
+    for cut in cuts.offsets() {
+        for dir in Direction::ALL {
+            let Some(next) = board.neighbour(cut, dir) else { continue };
+            let (a, b) = (node_of[cut], node_of[next]);
+            if b != usize::MAX && a != b && !neighbours[a].contains(&b) {
+                neighbours[a].push(b);
+                neighbours[b].push(a);
+            }
+        }
+    }
+
+    // Walk the tree from the start, then add up the best branch of every node from the leaves
+    let root = node_of[start];
+    let mut visited = vec![false; weight.len()];
+    let mut order = vec![root];
+    visited[root] = true;
+    let mut i = 0;
+    while i < order.len() {
+        for &next in &neighbours[order[i]] {
+            if !visited[next] {
+                visited[next] = true;
+                order.push(next);
+            }
+        }
+        i += 1;
+    }
+
+    let mut best = vec![0; weight.len()];
+    let mut done = vec![false; weight.len()];
+    for &node in order.iter().rev() {
+        let branch = neighbours[node].iter().filter(|&&next| done[next]).map(|&next| best[next]).max();
+        best[node] = weight[node] + branch.unwrap_or(0);
+        done[node] = true;
+    }
+    best[root]
+}
+
+/// Whether no opponent can reach any cell we can reach, so nothing they do matters to us
+/// anymore. True as well if there are no opponents left.
+pub fn is_isolated(round: &GameRound) -> bool {
+    let board = &round.board;
+    let head = round.offset(round.x, round.y);
+    let mut seed = board.no_cells();
+    seed.insert(head);
+    let mut within = board.free();
+    within.insert(head);
+    let touched = analysis::flood(seed, &within).spread();
+
+    round
+        .head_offsets()
+        .filter(|&(id, _)| id != round.player_id)
+        .all(|(_, offset)| !touched.contains(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 9x3 map with walls at x = 0 and x = 4 except for a door at (4, 1), we are at (2, 1).
+    fn two_rooms() -> GameRound {
+        let mut round = GameRound::new(0, 9, 3);
+        round.move_player(0, 2, 1);
+        for y in 0..3 {
+            round.board.take(round.offset(0, y), 1);
+            if y != 1 {
+                round.board.take(round.offset(4, y), 1);
+            }
+        }
+        round
+    }
+
+    #[test]
+    fn doors_split_rooms_into_chambers() {
+        let round = two_rooms();
+        let region = analysis::region(&round, round.offset(1, 1));
+
+        // The door, and the cells on either side that are the only way to it
+        let cuts = articulation_points(&round, &region);
+        let expected: Vec<_> = (3..6).map(|x| round.offset(x, 1)).collect();
+        assert_eq!(cuts.offsets().collect::<Vec<_>>(), expected);
+
+        let split = chambers(&round, &region);
+        let mut sizes: Vec<_> = split.chambers.iter().map(CellSet::count).collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![7, 11]);
+        assert_eq!(fillable_area(&round, round.offset(1, 1)), 21);
+    }
+
+    #[test]
+    fn dead_ends_count_only_once() {
+        let round = two_rooms();
+        // From next to the door: either the rest of the right room or the left one, not both
+        let start = round.offset(5, 1);
+        assert_eq!(analysis::reachable_area(&round, start), 21);
+        assert_eq!(fillable_area(&round, start), 1 + 11);
+    }
+
+    #[test]
+    fn isolated_once_nobody_else_can_get_in() {
+        let mut round = two_rooms();
+        round.move_player(2, 6, 1);
+        assert!(!is_isolated(&round));
+
+        round.board.take(round.offset(4, 1), 1);
+        assert!(is_isolated(&round));
+    }
+}
@@ -0,0 +1,200 @@
+//! Talking to the other players. Everything we say goes through a rate limit, since servers
+//! tend to kick clients that flood the chat.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use snek_protocol::Command;
+
+use crate::chamber;
+use crate::config::ChatConfig;
+use crate::connection::Transport;
+use crate::error::ClientError;
+use crate::GameRound;
+
+/// Say `reply` when someone else says something containing `contains`, ignoring case.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Response {
+    pub contains: String,
+    pub reply: String,
+}
+
+/// Decides whether and how to reply to what other players say.
+pub trait Responder: Send {
+    fn respond(&mut self, round: &GameRound, player_id: usize, msg: &str) -> Option<String>;
+}
+
+/// Replies with the first of a list of [`Response`]s that matches.
+pub struct Script {
+    responses: Vec<Response>,
+}
+
+impl Script {
+    pub fn new(responses: Vec<Response>) -> Script {
+        Script { responses }
+    }
+}
+
+impl Responder for Script {
+    fn respond(&mut self, _round: &GameRound, _player_id: usize, msg: &str) -> Option<String> {
+        let msg = msg.to_lowercase();
+        self.responses
+            .iter()
+            .find(|response| msg.contains(&response.contains.to_lowercase()))
+            .map(|response| response.reply.clone())
+    }
+}
+
+/// Allows `burst` messages at once, and one more every `interval` after that.
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    burst: u32,
+    interval: Duration,
+    /// Messages we may send right now, as of `updated`. Fractions build up to the next one.
+    allowance: f64,
+    updated: Instant,
+}
+
+impl RateLimit {
+    pub fn new(burst: u32, interval: Duration, now: Instant) -> RateLimit {
+        RateLimit { burst, interval, allowance: burst as f64, updated: now }
+    }
+
+    /// Whether a message may be sent at `now`, using up the allowance for it if so.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        let regained = now.saturating_duration_since(self.updated).as_secs_f64() / self.interval.as_secs_f64();
+        self.allowance = (self.allowance + regained).min(self.burst as f64);
+        self.updated = now;
+        if self.allowance < 1.0 {
+            return false;
+        }
+        self.allowance -= 1.0;
+        true
+    }
+}
+
+/// The chat of one session: what others said, and what we say back.
+pub struct Chat {
+    sealed: Option<String>,
+    won: Option<String>,
+    responder: Option<Box<dyn Responder>>,
+    limit: RateLimit,
+    /// Whether we said `sealed` this round already.
+    announced_sealed: bool,
+    /// Everything other players said, per player id. Ids are only valid within a round, so
+    /// this is cleared when a new one starts.
+    heard: BTreeMap<usize, Vec<String>>,
+}
+
+impl Chat {
+    pub fn new(config: &ChatConfig) -> Chat {
+        Chat {
+            sealed: config.sealed.clone(),
+            won: config.won.clone(),
+            responder: (!config.responses.is_empty())
+                .then(|| Box::new(Script::new(config.responses.clone())) as Box<dyn Responder>),
+            limit: RateLimit::new(config.burst, config.interval, Instant::now()),
+            announced_sealed: false,
+            heard: BTreeMap::new(),
+        }
+    }
+
+    /// Replaces the responder, e.g. with one that knows more than a list of replies.
+    pub fn set_responder(&mut self, responder: Box<dyn Responder>) {
+        self.responder = Some(responder);
+    }
+
+    pub fn new_round(&mut self) {
+        self.announced_sealed = false;
+        self.heard.clear();
+    }
+
+    /// What player `id` said this round.
+    pub fn heard_from(&self, id: usize) -> &[String] {
+        self.heard.get(&id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Sends `msg` unless we've been talking too much. Line breaks would end the command
+    /// early, so they become spaces.
+    pub fn say(&mut self, conn: &mut impl Transport, msg: &str) -> Result<(), ClientError> {
+        let msg = msg.replace(['\r', '\n'], " ");
+        if !self.limit.allow(Instant::now()) {
+            log::debug!("Not saying '{msg}', we've been talking too much");
+            return Ok(());
+        }
+        log::debug!("Saying '{msg}'");
+        conn.send(&Command::Chat(msg))
+    }
+
+    /// Player `id` said `msg`, which we note and maybe reply to.
+    pub fn heard(&mut self, conn: &mut impl Transport, round: &GameRound, id: usize, msg: String) -> Result<(), ClientError> {
+        if id == round.player_id {
+            return Ok(());
+        }
+        log::info!("Player {id} says: {msg}");
+        let reply = self.responder.as_mut().and_then(|responder| responder.respond(round, id, &msg));
+        self.heard.entry(id).or_default().push(msg);
+        match reply {
+            Some(reply) => self.say(conn, &reply),
+            None => Ok(()),
+        }
+    }
+
+    /// Says what there is to say about the round after a tick.
+    pub fn after_tick(&mut self, conn: &mut impl Transport, round: &GameRound) -> Result<(), ClientError> {
+        let Some(sealed) = self.sealed.clone() else { return Ok(()) };
+        // Being alone from the start isn't worth mentioning
+        if self.announced_sealed || round.alive_players < 2 || !chamber::is_isolated(round) {
+            return Ok(());
+        }
+        self.announced_sealed = true;
+        self.say(conn, &sealed)
+    }
+
+    /// Says what there is to say after winning, which not every server passes on, see
+    /// [`ChatConfig::won`].
+    pub fn after_win(&mut self, conn: &mut impl Transport) -> Result<(), ClientError> {
+        match self.won.clone() {
+            Some(won) => self.say(conn, &won),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limit_allows_bursts_then_one_per_interval() {
+        let start = Instant::now();
+        let mut limit = RateLimit::new(2, Duration::from_secs(4), start);
+        assert!(limit.allow(start));
+        assert!(limit.allow(start));
+        assert!(!limit.allow(start));
+
+        assert!(!limit.allow(start + Duration::from_secs(3)));
+        assert!(limit.allow(start + Duration::from_secs(4)));
+        assert!(!limit.allow(start + Duration::from_secs(5)));
+
+        // Quiet for long enough to allow a burst again, but no more
+        let later = start + Duration::from_secs(60);
+        assert!(limit.allow(later));
+        assert!(limit.allow(later));
+        assert!(!limit.allow(later));
+    }
+
+    #[test]
+    fn script_replies_to_the_first_match() {
+        let mut script = Script::new(vec![
+            Response { contains: "GG".to_string(), reply: "gg wp".to_string() },
+            Response { contains: "g".to_string(), reply: "?".to_string() },
+        ]);
+        let round = GameRound::new(0, 4, 4);
+        assert_eq!(script.respond(&round, 1, "gg everyone"), Some("gg wp".to_string()));
+        assert_eq!(script.respond(&round, 1, "go"), Some("?".to_string()));
+        assert_eq!(script.respond(&round, 1, "hi"), None);
+    }
+}
@@ -0,0 +1,302 @@
+use std::sync::mpsc;
+use std::thread;
+
+use snek_protocol::{Command, Direction, Message};
+
+use crate::chat::Chat;
+use crate::config::Config;
+use crate::connection::Transport;
+use crate::deadline::Deadline;
+use crate::error::ClientError;
+use crate::logging;
+use crate::opponent;
+use crate::render::{self, Render};
+use crate::stats::{self, Death, Outcome, RoundRecord};
+use crate::strategy::{self, Strategy};
+use crate::telemetry::{Telemetry, TickRecord};
+use crate::GameRound;
+
+fn send_move(conn: &mut impl Transport, info: &mut GameRound, dir: Direction) -> Result<(), ClientError> {
+    conn.send(&Command::Move(dir))?;
+    info.direction = dir;
+    Ok(())
+}
+
+/// Lets the strategy decide on its own thread, so that a fallback can be sent when it takes
+/// too long. A strategy that ignores its deadline still holds up the next tick, since we
+/// have to wait for it to hand back the round.
+fn decide(
+    conn: &mut impl Transport,
+    info: &mut GameRound,
+    strategy: &mut dyn Strategy,
+    config: &Config,
+    telemetry: Option<&mut Telemetry>,
+) -> Result<(), ClientError> {
+    let deadline = Deadline::new(config.tick_budget);
+    let (decision, decided) = mpsc::channel();
+    let mut record = TickRecord::new(&config.username, info.player_id, info.tick, info.alive_players, strategy.name());
+
+    let (fallback, decision) = thread::scope(|scope| -> Result<_, ClientError> {
+        let round = &*info;
+        let strategy = &mut *strategy;
+        let bot = logging::current();
+        scope.spawn(move || {
+            logging::scoped(bot, || {
+                let _ = decision.send(strategy.next_move(round, &deadline));
+            });
+        });
+
+        if let Ok(dir) = decided.recv_timeout(config.fallback_after) {
+            return Ok((None, Some(dir)));
+        }
+
+        let fallback = strategy::safe_move(round);
+        conn.send(&Command::Move(fallback))?;
+        Ok((Some(fallback), decided.recv_timeout(deadline.remaining()).ok()))
+    })?;
+
+    record.elapsed_ms = deadline.elapsed().as_secs_f64() * 1000.0;
+    record.budget_used = deadline.used();
+    record.direction = decision.map(|dir| dir.as_str());
+    record.fallback = fallback.map(|dir| dir.as_str());
+    record.set_scores(&strategy.scores());
+
+    match (fallback, decision) {
+        (None, Some(dir)) => {
+            send_move(conn, info, dir)?;
+            log::debug!("{record}");
+        }
+        (Some(fallback), Some(dir)) => {
+            info.direction = fallback;
+            if dir != fallback {
+                send_move(conn, info, dir)?;
+            }
+            log::warn!("{record}");
+        }
+        (Some(fallback), None) => {
+            info.direction = fallback;
+            log::warn!("{record}");
+        }
+        (None, None) => unreachable!("Either the strategy decided or the fallback was sent"),
+    }
+
+    if let Some(telemetry) = telemetry {
+        telemetry.write(&record);
+    }
+    Ok(())
+}
+
+pub fn round_loop(
+    conn: &mut impl Transport,
+    mut info: GameRound,
+    strategy: &mut dyn Strategy,
+    config: &Config,
+    mut telemetry: Option<&mut Telemetry>,
+    chat: &mut Chat,
+) -> Result<(), ClientError> {
+    let mut death = None;
+    chat.new_round();
+    log::debug!("Starting a new round. Player {}, Map: {}x{}, Strategy: {}", info.player_id, info.width, info.height, strategy.name());
+
+    loop {
+        let Some(msg) = conn.read_message()? else { return Ok(()) };
+        match msg {
+            Message::Pos { player_id, x, y } => {
+                if x >= info.width || y >= info.height {
+                    log::warn!("Ignoring position {x},{y} of player {player_id}, it's off the map");
+                    continue;
+                }
+                opponent::observe(&mut info, player_id, x, y);
+                info.move_player(player_id, x, y);
+            }
+
+            Message::Tick => {
+                // In the first tick we need to collect information on who
+                // we are playing against, so it gets special handling.
+                if info.first_tick {
+                    info.first_tick = false;
+                    strategy.new_round(&info);
+                }
+                info.tick += 1;
+
+                decide(conn, &mut info, strategy, config, telemetry.as_deref_mut())?;
+                if let Some(msg) = strategy.chat(&info) {
+                    chat.say(conn, &msg)?;
+                }
+                chat.after_tick(conn, &info)?;
+                if config.render != Render::Off {
+                    log::debug!(target: "snek::render", "Tick {}\n{}", info.tick, render::render(&info, config.render).trim_end());
+                }
+            }
+
+            Message::Die(ids) => {
+                if ids.contains(&info.player_id) {
+                    death = Some(stats::cause_of_death(&info));
+                }
+                info.remove_players(&ids);
+                log::debug!("Players left alive: {}", info.alive_players);
+            }
+
+            Message::Win { wins, losses } => {
+                log::info!("Won! ({wins} wins, {losses} losses)");
+                log_opponents(&info);
+                record_result(&info, strategy, config, Outcome::Won, None);
+                chat.after_win(conn)?;
+                return Ok(())
+            }
+
+            Message::Lose { wins, losses } => {
+                log::info!("Lost! ({wins} wins, {losses} losses)");
+                log_opponents(&info);
+                record_result(&info, strategy, config, Outcome::Lost, death);
+                return Ok(())
+            }
+
+            Message::Message { player_id, msg } => chat.heard(conn, &info, player_id, msg)?,
+
+            msg => log::trace!("Unhandled message in round loop: {:?}", msg),
+        }
+    }
+}
+
+/// What we made of everyone we played against.
+fn log_opponents(info: &GameRound) {
+    for (id, model) in info.opponents.iter().enumerate().filter(|(_, model)| model.moves_seen > 0) {
+        let (behaviour, chance) = model.likeliest();
+        log::debug!("Player {id} seems to play '{}' ({:.0}% after {} moves)", behaviour.as_str(), chance * 100.0, model.moves_seen);
+    }
+}
+
+/// Adds the round to the stats store, if there is one.
+fn record_result(info: &GameRound, strategy: &dyn Strategy, config: &Config, outcome: Outcome, death: Option<Death>) {
+    let Some(path) = &config.stats else { return };
+    let record = RoundRecord::new(info, &config.username, strategy.name(), outcome, death);
+    if let Err(err) = stats::append(path, &record) {
+        log::warn!("Failed to write to {}: {err}", path.display());
+    }
+}
+
+/// Joins the game once the server greets us and plays rounds until the messages run out
+/// or the server sends an error.
+pub fn session_loop(conn: &mut impl Transport, join: &Command, strategy: &mut dyn Strategy, config: &Config) -> Result<(), ClientError> {
+    let mut telemetry = config.telemetry.as_deref().and_then(|path| match Telemetry::open(path) {
+        Ok(telemetry) => {
+            log::info!("Writing telemetry to {}", telemetry.path().display());
+            Some(telemetry)
+        }
+        Err(err) => {
+            log::warn!("No telemetry this session, can't open {}: {err}", path.display());
+            None
+        }
+    });
+    let mut chat = Chat::new(&config.chat);
+
+    while let Some(msg) = conn.read_message()? {
+        match msg {
+            Message::Game { width, height, player_id } => {
+                let round = GameRound::with_rules(player_id, width, height, config.rules);
+                round_loop(conn, round, strategy, config, telemetry.as_mut(), &mut chat)?
+            }
+
+            Message::Error(msg) => return Err(ClientError::Server(msg)),
+
+            Message::Motd(msg) => {
+                log::debug!("MOTD: {msg}");
+                conn.send(join)?;
+            }
+
+            x => log::trace!("ignoring: {:?}", x),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Keeps what is sent, has nothing to say.
+    #[derive(Default)]
+    struct Sent(Vec<String>);
+
+    impl Transport for Sent {
+        fn read_message(&mut self) -> Result<Option<Message>, ClientError> {
+            Ok(None)
+        }
+
+        fn send(&mut self, cmd: &Command) -> Result<(), ClientError> {
+            self.0.push(cmd.to_string());
+            Ok(())
+        }
+    }
+
+    /// Takes `delay` to decide on `dir`, whatever the deadline says. Remembers which bot it
+    /// thought for.
+    struct Slow {
+        delay: Duration,
+        dir: Direction,
+        bot: Option<Arc<str>>,
+    }
+
+    impl Strategy for Slow {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        fn next_move(&mut self, _round: &GameRound, _deadline: &Deadline) -> Direction {
+            thread::sleep(self.delay);
+            self.bot = logging::current();
+            self.dir
+        }
+    }
+
+    /// The lines sent while deciding on the first move, on an empty map where the fallback
+    /// is to keep going up, and the direction we end up going in.
+    fn decide_with(delay_ms: u64, dir: Direction) -> (Vec<String>, Direction) {
+        let config = Config {
+            tick_budget: Duration::from_millis(200),
+            fallback_after: Duration::from_millis(20),
+            ..Config::default()
+        };
+        let mut round = GameRound::new(0, 8, 8);
+        round.move_player(0, 3, 3);
+        assert_eq!(strategy::safe_move(&round), Direction::Up);
+
+        let mut sent = Sent::default();
+        let mut strategy = Slow { delay: Duration::from_millis(delay_ms), dir, bot: None };
+        decide(&mut sent, &mut round, &mut strategy, &config, None).unwrap();
+        (sent.0, round.direction)
+    }
+
+    #[test]
+    fn quick_decisions_are_sent_alone() {
+        assert_eq!(decide_with(0, Direction::Left), (vec!["move|left".to_string()], Direction::Left));
+    }
+
+    #[test]
+    fn slow_decisions_follow_the_fallback() {
+        let expected = vec!["move|up".to_string(), "move|left".to_string()];
+        assert_eq!(decide_with(60, Direction::Left), (expected, Direction::Left));
+        // Nothing to correct if the strategy agrees with the fallback
+        assert_eq!(decide_with(60, Direction::Up), (vec!["move|up".to_string()], Direction::Up));
+    }
+
+    #[test]
+    fn strategies_think_for_the_bot_that_asked() {
+        let mut round = GameRound::new(0, 8, 8);
+        round.move_player(0, 3, 3);
+        let mut strategy = Slow { delay: Duration::ZERO, dir: Direction::Up, bot: None };
+        let bot: Arc<str> = Arc::from("snek-a");
+
+        logging::scoped(Some(bot.clone()), || decide(&mut Sent::default(), &mut round, &mut strategy, &Config::default(), None)).unwrap();
+        assert_eq!(strategy.bot, Some(bot));
+    }
+
+    #[test]
+    fn late_decisions_are_dropped() {
+        assert_eq!(decide_with(400, Direction::Left), (vec!["move|up".to_string()], Direction::Up));
+    }
+}
@@ -0,0 +1,105 @@
+//! Predicts where we might run into someone on the next tick. Free cells next to an opponent's
+//! head are contested: if they move there too, we die, and usually they do as well.
+
+use snek_protocol::{Direction, HeadOn};
+
+use crate::GameRound;
+
+/// The chance an opponent picks any one of their (usually three) possible moves.
+const CHANCE_PER_OPPONENT: f64 = 1.0 / 3.0;
+
+/// What moving into a cell may do to us.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Safety {
+    /// Nobody else can get there next tick.
+    Safe,
+    /// Free, but this many opponents can move there as well and win the cell or die with us.
+    Contested(usize),
+    /// Taken already, moving there kills us.
+    Lethal,
+}
+
+impl Safety {
+    /// How bad the move is, from 0 for safe to 1 for certain death, with `alive` players left
+    /// including us. Contested cells count by the chance that any of the opponents goes there.
+    /// In a 1v1 where a head-on collision takes the opponent with us, it's a draw and only half
+    /// as bad as losing. With more players left, it's as bad as any other death.
+    pub fn cost(self, alive: usize, head_on: HeadOn) -> f64 {
+        match self {
+            Safety::Safe => 0.0,
+
+            Safety::Contested(opponents) => {
+                let hit = 1.0 - (1.0 - CHANCE_PER_OPPONENT).powi(opponents as i32);
+                if alive <= 2 && head_on == HeadOn::AllDie { hit / 2.0 } else { hit }
+            }
+
+            Safety::Lethal => 1.0,
+        }
+    }
+}
+
+/// Per cell, how many opponents could move there on the next tick. Opponents we'd win the
+/// cell against don't count.
+pub fn opponent_reach(round: &GameRound) -> Vec<usize> {
+    let us = round.player_id;
+    let mut reach = vec![0; round.board.cells()];
+    let threats = round.head_offsets().filter(|&(id, _)| id != us && round.rules.head_on.survivor([us, id]) != Some(us));
+    for (_, head) in threats {
+        for next in Direction::ALL.into_iter().filter_map(|dir| round.free_neighbour(head, dir)) {
+            reach[next] += 1;
+        }
+    }
+    reach
+}
+
+/// What moving from our head in `dir` may do to us.
+pub fn classify(round: &GameRound, reach: &[usize], dir: Direction) -> Safety {
+    let Some(next) = round.next_offset(round.x, round.y, dir).filter(|&next| round.is_free(next)) else {
+        return Safety::Lethal;
+    };
+    if reach[next] > 0 {
+        Safety::Contested(reach[next])
+    } else {
+        Safety::Safe
+    }
+}
+
+/// The safety of every direction we could move in.
+pub fn classify_moves(round: &GameRound) -> Vec<(Direction, Safety)> {
+    let reach = opponent_reach(round);
+    Direction::ALL
+        .into_iter()
+        .map(|dir| (dir, classify(round, &reach, dir)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_next_to_opponents_are_contested() {
+        let mut round = GameRound::new(0, 7, 7);
+        round.move_player(0, 2, 3);
+        round.move_player(1, 4, 3);
+        round.move_player(2, 3, 5);
+        let trail = round.offset(2, 2);
+        round.board.take(trail, 2);
+
+        let moves = classify_moves(&round);
+        assert_eq!(moves[Direction::Up as usize], (Direction::Up, Safety::Lethal));
+        assert_eq!(moves[Direction::Right as usize], (Direction::Right, Safety::Contested(1)));
+        assert_eq!(moves[Direction::Down as usize], (Direction::Down, Safety::Safe));
+        assert_eq!(moves[Direction::Left as usize], (Direction::Left, Safety::Safe));
+    }
+
+    #[test]
+    fn head_on_in_a_1v1_costs_less() {
+        let contested = Safety::Contested(1);
+        assert!(contested.cost(2, HeadOn::AllDie) < contested.cost(3, HeadOn::AllDie));
+        assert!(Safety::Contested(2).cost(4, HeadOn::AllDie) > contested.cost(4, HeadOn::AllDie));
+        assert_eq!(contested.cost(2, HeadOn::LowestIdWins), contested.cost(3, HeadOn::AllDie));
+        assert_eq!(Safety::Lethal.cost(2, HeadOn::AllDie), 1.0);
+        assert_eq!(Safety::Safe.cost(5, HeadOn::AllDie), 0.0);
+    }
+}
@@ -0,0 +1,458 @@
+//! Settings for running the bot. They come from a TOML profile file, with command line
+//! flags taking precedence over it and [`Config::default`] filling in the rest.
+
+use std::fs;
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use rand::Rng;
+use serde::Deserialize;
+use snek_protocol::Rules;
+
+use crate::chat::Response;
+use crate::render::Render;
+
+/// How much the bot logs while playing. Sets the level of the logger, which `RUST_LOG`
+/// overrides, e.g. `RUST_LOG=snek::client=trace`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verbosity {
+    /// Only errors.
+    Silent,
+    /// Also round results, connections and warnings, like fallback moves.
+    Quiet,
+    /// Also every tick and the board after it.
+    Normal,
+    /// Also every message we don't act on.
+    Verbose,
+}
+
+impl Verbosity {
+    pub fn level(self) -> log::LevelFilter {
+        match self {
+            Verbosity::Silent => log::LevelFilter::Error,
+            Verbosity::Quiet => log::LevelFilter::Info,
+            Verbosity::Normal => log::LevelFilter::Debug,
+            Verbosity::Verbose => log::LevelFilter::Trace,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Wait before reconnecting, doubled for every attempt in a row that failed.
+    pub delay: Duration,
+    /// The wait doesn't grow past this.
+    pub max_delay: Duration,
+    /// Give up after this many connection attempts in a row failed. Retries forever if `None`.
+    pub max_attempts: Option<u32>,
+}
+
+impl ReconnectPolicy {
+    /// How long to wait after `failures` attempts in a row failed. Picked at random between
+    /// half and all of the exponential delay, so bots that lost the server together don't all
+    /// come back at the same moment.
+    pub fn backoff(&self, failures: u32, rng: &mut impl Rng) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        let delay = self.delay.saturating_mul(factor).min(self.max_delay);
+        delay.mul_f64(rng.gen_range(0.5..=1.0))
+    }
+}
+
+/// What to say when, and how often we may say anything at all.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatConfig {
+    /// Said once per round when no opponent can reach us anymore.
+    pub sealed: Option<String>,
+    /// Said when we win a round. The round is over by then, so servers that only pass on
+    /// what players in a round say, like snek-server, drop it. Strategies that see a win
+    /// coming can say so in time, see [`Strategy::chat`](crate::strategy::Strategy::chat).
+    pub won: Option<String>,
+    /// Replies to messages from other players, see [`Script`](crate::chat::Script).
+    pub responses: Vec<Response>,
+    /// How many messages we may send in a row.
+    pub burst: u32,
+    /// After the burst, one more message per this long.
+    pub interval: Duration,
+}
+
+impl Default for ChatConfig {
+    fn default() -> ChatConfig {
+        ChatConfig {
+            sealed: None,
+            won: None,
+            responses: vec![],
+            burst: 3,
+            interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// One of several bots played from the same process. Whatever isn't given here is taken
+/// from the rest of the [`Config`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bot {
+    pub username: String,
+    pub password: Option<String>,
+    pub strategy: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    pub strategy: String,
+    pub verbosity: Verbosity,
+    /// How to draw the board after each tick, when `verbosity` is at least normal.
+    pub render: Render,
+    pub reconnect: ReconnectPolicy,
+    /// How long the strategy may think about a move, counted from when the tick arrives.
+    pub tick_budget: Duration,
+    /// When the strategy hasn't decided after this long, a fallback move is sent
+    /// while it keeps thinking until the budget runs out.
+    pub fallback_after: Duration,
+    /// Where sessions are recorded to. Nothing is recorded if `None`.
+    pub recordings: Option<PathBuf>,
+    /// File to append the result of every round to, see [`stats`](crate::stats).
+    pub stats: Option<PathBuf>,
+    /// File to append a [`TickRecord`](crate::telemetry::TickRecord) to on every tick.
+    pub telemetry: Option<PathBuf>,
+    pub chat: ChatConfig,
+    /// The rules of the server we play on, it doesn't tell.
+    pub rules: Rules,
+    /// Bots to play at the same time. If empty, there is only the one `username` plays as.
+    pub bots: Vec<Bot>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            host: "127.0.0.1".to_string(),
+            port: 4000,
+            username: "Snekisnek".to_string(),
+            password: "jkasdfjkshdfjksdfkjhsdkjhfsdjk".to_string(),
+            strategy: "simple".to_string(),
+            verbosity: Verbosity::Normal,
+            render: Render::Color,
+            reconnect: ReconnectPolicy {
+                delay: Duration::from_secs(2),
+                max_delay: Duration::from_secs(60),
+                max_attempts: None,
+            },
+            tick_budget: Duration::from_millis(200),
+            fallback_after: Duration::from_millis(120),
+            recordings: Some(PathBuf::from("recordings")),
+            stats: Some(PathBuf::from("stats.jsonl")),
+            telemetry: None,
+            chat: ChatConfig::default(),
+            rules: Rules::default(),
+            bots: vec![],
+        }
+    }
+}
+
+/// A profile file. Everything is optional, missing settings keep their defaults.
+///
+/// ```toml
+/// host = "::1"
+/// port = 4000
+/// username = "Snekisnek"
+/// password = "hunter2"
+/// strategy = "territory"
+/// verbosity = "quiet"
+/// render = "plain"
+/// recordings = "recordings"
+/// stats = "stats.jsonl"
+/// telemetry = "telemetry.jsonl"
+///
+/// [reconnect]
+/// delay_secs = 5.0
+/// max_delay_secs = 120.0
+/// max_attempts = 10
+///
+/// [timing]
+/// tick_budget_ms = 200
+/// fallback_after_ms = 120
+///
+/// [chat]
+/// sealed = "Enjoy the view from over there"
+/// won = "gg"
+/// burst = 3
+/// interval_secs = 5.0
+///
+/// [[chat.responses]]
+/// contains = "gg"
+/// reply = "gg wp"
+///
+/// [rules]
+/// edges = "walls"
+/// clear_dead_trails = false
+/// head_on = "lowest-id-wins"
+///
+/// [[bots]]
+/// username = "snek-a"
+/// strategy = "minimax"
+///
+/// [[bots]]
+/// username = "snek-b"
+/// password = "hunter3"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub strategy: Option<String>,
+    pub verbosity: Option<Verbosity>,
+    pub render: Option<Render>,
+    pub recordings: Option<PathBuf>,
+    pub stats: Option<PathBuf>,
+    pub telemetry: Option<PathBuf>,
+    pub reconnect: ReconnectProfile,
+    pub timing: TimingProfile,
+    pub chat: ChatProfile,
+    pub rules: Option<Rules>,
+    pub bots: Vec<Bot>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReconnectProfile {
+    pub delay_secs: Option<f64>,
+    pub max_delay_secs: Option<f64>,
+    pub max_attempts: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimingProfile {
+    pub tick_budget_ms: Option<u64>,
+    pub fallback_after_ms: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChatProfile {
+    pub sealed: Option<String>,
+    pub won: Option<String>,
+    pub burst: Option<u32>,
+    pub interval_secs: Option<f64>,
+    pub responses: Vec<Response>,
+}
+
+impl Profile {
+    pub fn load(path: &Path) -> Result<Profile> {
+        let contents = fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Parsing {}", path.display()))
+    }
+
+    /// Overrides every setting of `config` this profile has a value for. Fails on values that
+    /// make no sense, leaving `config` partly changed.
+    pub fn apply(self, config: &mut Config) -> Result<()> {
+        if let Some(host) = self.host {
+            config.host = host;
+        }
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if let Some(username) = self.username {
+            config.username = username;
+        }
+        if let Some(password) = self.password {
+            config.password = password;
+        }
+        if let Some(strategy) = self.strategy {
+            config.strategy = strategy;
+        }
+        if let Some(verbosity) = self.verbosity {
+            config.verbosity = verbosity;
+        }
+        if let Some(render) = self.render {
+            config.render = render;
+        }
+        if let Some(recordings) = self.recordings {
+            config.recordings = Some(recordings);
+        }
+        if let Some(stats) = self.stats {
+            config.stats = Some(stats);
+        }
+        if let Some(telemetry) = self.telemetry {
+            config.telemetry = Some(telemetry);
+        }
+        if let Some(delay) = self.reconnect.delay_secs {
+            config.reconnect.delay = seconds("reconnect.delay_secs", delay)?;
+        }
+        if let Some(max_delay) = self.reconnect.max_delay_secs {
+            config.reconnect.max_delay = seconds("reconnect.max_delay_secs", max_delay)?;
+        }
+        if let Some(max_attempts) = self.reconnect.max_attempts {
+            config.reconnect.max_attempts = Some(max_attempts);
+        }
+        if let Some(budget) = self.timing.tick_budget_ms {
+            config.tick_budget = Duration::from_millis(budget);
+        }
+        if let Some(fallback_after) = self.timing.fallback_after_ms {
+            config.fallback_after = Duration::from_millis(fallback_after);
+        }
+        if self.timing.tick_budget_ms.is_some() || self.timing.fallback_after_ms.is_some() {
+            config.check_timing()?;
+        }
+        if let Some(sealed) = self.chat.sealed {
+            config.chat.sealed = Some(sealed);
+        }
+        if let Some(won) = self.chat.won {
+            config.chat.won = Some(won);
+        }
+        if let Some(burst) = self.chat.burst {
+            config.chat.burst = burst;
+        }
+        if let Some(interval) = self.chat.interval_secs {
+            config.chat.interval = seconds("chat.interval_secs", interval)?;
+        }
+        if !self.chat.responses.is_empty() {
+            config.chat.responses = self.chat.responses;
+        }
+        if let Some(rules) = self.rules {
+            config.rules = rules;
+        }
+        if !self.bots.is_empty() {
+            config.bots = self.bots;
+        }
+        Ok(())
+    }
+}
+
+/// `secs` as a duration, or an error naming the setting `name` if it's negative or not a
+/// number at all.
+pub fn seconds(name: &str, secs: f64) -> Result<Duration> {
+    Duration::try_from_secs_f64(secs).with_context(|| format!("Invalid {name} {secs}, expected a number of seconds"))
+}
+
+impl Config {
+    /// Fails if the strategy gets no time to think, or the fallback move would only be sent
+    /// once the tick budget is used up.
+    pub fn check_timing(&self) -> Result<()> {
+        if self.tick_budget.is_zero() {
+            bail!("The tick budget must be more than 0ms");
+        }
+        if self.fallback_after >= self.tick_budget {
+            bail!(
+                "The fallback move has to be sent before the tick budget of {}ms is used up, not after {}ms",
+                self.tick_budget.as_millis(),
+                self.fallback_after.as_millis(),
+            );
+        }
+        Ok(())
+    }
+
+    /// The settings of every bot to play, see [`Config::bots`].
+    pub fn per_bot(&self) -> Vec<Config> {
+        if self.bots.is_empty() {
+            return vec![self.clone()];
+        }
+        self.bots
+            .iter()
+            .map(|bot| Config {
+                username: bot.username.clone(),
+                password: bot.password.clone().unwrap_or_else(|| self.password.clone()),
+                strategy: bot.strategy.clone().unwrap_or_else(|| self.strategy.clone()),
+                bots: vec![],
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// The addresses to try for the game server. IPv6 addresses may be given with or without
+    /// the brackets used in URLs, i.e. as `::1` or `[::1]`.
+    pub fn server_addrs(&self) -> io::Result<Vec<SocketAddr>> {
+        let host = self.host.trim_start_matches('[').trim_end_matches(']');
+        match host.parse::<IpAddr>() {
+            Ok(ip) => Ok(vec![SocketAddr::new(ip, self.port)]),
+            Err(_) => Ok((host, self.port).to_socket_addrs()?.collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit() {
+        let policy = ReconnectPolicy {
+            delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(30),
+            max_attempts: None,
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for (failures, full) in [(0, 2), (1, 2), (2, 4), (3, 8), (4, 16), (5, 30), (40, 30)] {
+            let full = Duration::from_secs(full);
+            let delay = policy.backoff(failures, &mut rng);
+            assert!(delay >= full / 2 && delay <= full, "{failures} failures: {delay:?}");
+        }
+    }
+
+    #[test]
+    fn bots_take_what_they_dont_set_from_the_config() {
+        let config = Config {
+            bots: vec![
+                Bot { username: "a".to_string(), password: None, strategy: Some("minimax".to_string()) },
+                Bot { username: "b".to_string(), password: Some("hunter3".to_string()), strategy: None },
+            ],
+            ..Config::default()
+        };
+        let bots = config.per_bot();
+        assert_eq!(bots.len(), 2);
+        assert_eq!((bots[0].username.as_str(), bots[0].strategy.as_str()), ("a", "minimax"));
+        assert_eq!(bots[0].password, config.password);
+        assert_eq!((bots[1].password.as_str(), bots[1].strategy.as_str()), ("hunter3", "simple"));
+        assert!(bots.iter().all(|bot| bot.bots.is_empty() && bot.port == config.port));
+
+        assert_eq!(Config::default().per_bot(), vec![Config::default()]);
+    }
+
+    #[test]
+    fn the_example_profile_applies() {
+        let profile: Profile = toml::from_str(include_str!("../profile.example.toml")).unwrap();
+        let mut config = Config::default();
+        profile.apply(&mut config).unwrap();
+        assert_eq!(config.strategy, "territory");
+        assert_eq!(config.rules, Rules::default());
+    }
+
+    #[test]
+    fn rejects_negative_delays() {
+        let profile: Profile = toml::from_str("[reconnect]\ndelay_secs = -1.0").unwrap();
+        assert!(profile.apply(&mut Config::default()).is_err());
+        let profile: Profile = toml::from_str("[chat]\ninterval_secs = -5.0").unwrap();
+        assert!(profile.apply(&mut Config::default()).is_err());
+        assert!(seconds("delay", f64::NAN).is_err());
+        assert_eq!(seconds("delay", 1.5).unwrap(), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn the_fallback_comes_before_the_budget_is_used_up() {
+        for timing in ["tick_budget_ms = 0", "tick_budget_ms = 100\nfallback_after_ms = 100", "fallback_after_ms = 500"] {
+            let profile: Profile = toml::from_str(&format!("[timing]\n{timing}")).unwrap();
+            assert!(profile.apply(&mut Config::default()).is_err(), "{timing}");
+        }
+
+        let profile: Profile = toml::from_str("[timing]\ntick_budget_ms = 100\nfallback_after_ms = 60").unwrap();
+        let mut config = Config::default();
+        profile.apply(&mut config).unwrap();
+        assert_eq!((config.tick_budget, config.fallback_after), (Duration::from_millis(100), Duration::from_millis(60)));
+        assert!(Config::default().check_timing().is_ok());
+    }
+}
@@ -0,0 +1,66 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+
+use snek_protocol::{Command, Message};
+
+use crate::error::ClientError;
+use crate::recorder::Recorder;
+
+/// Where the client gets its messages from and sends its commands to.
+pub trait Transport {
+    /// The next message from the server, or `None` once there are no more.
+    fn read_message(&mut self) -> Result<Option<Message>, ClientError>;
+
+    fn send(&mut self, cmd: &Command) -> Result<(), ClientError>;
+}
+
+/// A connection to the game server, optionally recording everything that goes over it.
+pub struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    recorder: Option<Recorder>,
+}
+
+impl Connection {
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Connection> {
+        let writer = TcpStream::connect(addr)?;
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(Connection { reader, writer, recorder: None })
+    }
+
+    /// Records all traffic from here on.
+    pub fn record(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+}
+
+impl Transport for Connection {
+    /// Skips over lines that aren't valid messages, a single garbled line shouldn't cost us the round.
+    fn read_message(&mut self) -> Result<Option<Message>, ClientError> {
+        loop {
+            let mut line = String::new();
+            let size = self.reader.read_line(&mut line)?;
+            if size == 0 {
+                // The server closed the connection
+                return Ok(None);
+            }
+            if let Some(recorder) = self.recorder.as_mut() {
+                recorder.received(&line);
+            }
+
+            match line.parse::<Message>() {
+                Ok(msg) => return Ok(Some(msg)),
+                Err(err) => log::warn!("Skipping message from game server: {err}"),
+            }
+        }
+    }
+
+    fn send(&mut self, cmd: &Command) -> Result<(), ClientError> {
+        let line = format!("{cmd}\n");
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.sent(&line);
+        }
+        self.writer.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use std::time::{Duration, Instant};
+
+/// Share of the tick budget searches may use. The rest is left as headroom, so the answer
+/// arrives before the client gives up waiting and sends a fallback move.
+pub const TIME_SHARE: f64 = 0.5;
+
+/// The time a strategy has left to decide on its move for the current tick.
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    start: Instant,
+    budget: Duration,
+}
+
+impl Deadline {
+    pub fn new(budget: Duration) -> Deadline {
+        Deadline { start: Instant::now(), budget }
+    }
+
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.budget.saturating_sub(self.elapsed())
+    }
+
+    pub fn expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// How much of the budget is used up, from 0 to 1 (and beyond, if we overran it).
+    pub fn used(&self) -> f64 {
+        self.elapsed().as_secs_f64() / self.budget.as_secs_f64()
+    }
+}
@@ -0,0 +1,172 @@
+//! Making the most of a region nobody else can reach: the longer the path we take through it,
+//! the longer we outlast everyone else.
+
+use snek_protocol::{Direction, Edges};
+
+use crate::analysis;
+use crate::board::CellSet;
+use crate::deadline::{Deadline, TIME_SHARE};
+use crate::GameRound;
+
+/// How often to look at the clock, in visited positions.
+const CLOCK_INTERVAL: usize = 1024;
+
+/// At most how many cells a path of cells in `region` can visit, starting with `start`.
+///
+/// If the map has walls or even dimensions, moving always changes the colour of the cell on
+/// a checkerboard, so a path can't visit more than one cell of the start's colour per cell of
+/// the other colour, plus the start. When moves wrap around a map with odd dimensions, the
+/// colours don't line up at the edges, and the bound is just the number of cells.
+pub fn parity_bound(round: &GameRound, region: &CellSet, start: usize) -> usize {
+    let cells = region.count();
+    let even = round.width.is_multiple_of(2) && round.height.is_multiple_of(2);
+    if round.rules.edges == Edges::Wrap && !even {
+        return cells;
+    }
+
+    let colour = |offset: usize| {
+        let (x, y) = round.position(offset);
+        (x + y) % 2
+    };
+    let same = region.offsets().filter(|&offset| colour(offset) == colour(start)).count();
+    let other = cells - same;
+    (2 * same).min(2 * other + 1)
+}
+
+/// The longest path found from our head, as moves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    pub moves: Vec<Direction>,
+    /// Whether there is no longer path. If the search ran out of time, there might be.
+    pub exact: bool,
+}
+
+/// Searches the longest path of free cells from our head, exhaustively unless it runs out of
+/// time. Moves along walls are tried first, so even an unfinished search usually has a path
+/// that wastes little space.
+///
+/// This is only fast enough for small regions, the number of paths grows exponentially.
+pub fn longest_path(round: &GameRound, deadline: &Deadline) -> Path {
+    let head = round.offset(round.x, round.y);
+    let mut start = round.board.no_cells();
+    start.insert(head);
+    let free = round.board.free();
+    let mut region = analysis::flood(start.spread().and(&free), &free);
+    let bound = region.count();
+
+    let mut search = Search {
+        round,
+        deadline,
+        visited: 0,
+        path: vec![],
+        best: vec![],
+        bound,
+    };
+    let exact = !matches!(search.extend(head, &mut region), Err(Stop::OutOfTime));
+    Path { moves: search.best, exact }
+}
+
+/// The search gave up, either out of time or because it can't do better than what it has.
+enum Stop {
+    OutOfTime,
+    Optimal,
+}
+
+struct Search<'a> {
+    round: &'a GameRound,
+    deadline: &'a Deadline,
+    visited: usize,
+    path: Vec<Direction>,
+    best: Vec<Direction>,
+    /// No path can be longer than this.
+    bound: usize,
+}
+
+impl Search<'_> {
+    /// Tries every way to continue the current path from `cell` through the cells in `free`.
+    /// `free` is changed along the way but back as before once this returns.
+    fn extend(&mut self, cell: usize, free: &mut CellSet) -> Result<(), Stop> {
+        if self.path.len() > self.best.len() {
+            self.best = self.path.clone();
+            if self.best.len() == self.bound {
+                return Err(Stop::Optimal);
+            }
+        }
+
+        self.visited += 1;
+        if self.visited.is_multiple_of(CLOCK_INTERVAL) && self.deadline.used() >= TIME_SHARE {
+            return Err(Stop::OutOfTime);
+        }
+
+        let mut moves: Vec<(Direction, usize)> = Direction::ALL
+            .into_iter()
+            .filter_map(|dir| Some((dir, self.round.neighbour(cell, dir)?)))
+            .filter(|&(_, next)| free.contains(next))
+            .collect();
+        moves.sort_by_key(|&(_, next)| exits(self.round, free, next));
+
+        for (dir, next) in moves {
+            // Everything still reachable from `next` has to make for a longer path than the best
+            let mut seed = self.round.board.no_cells();
+            seed.insert(next);
+            let reachable = analysis::flood(seed, free);
+            if self.path.len() + parity_bound(self.round, &reachable, next) <= self.best.len() {
+                continue;
+            }
+
+            free.remove(next);
+            self.path.push(dir);
+            let result = self.extend(next, free);
+            self.path.pop();
+            free.insert(next);
+            result?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Number of cells in `free` next to `offset`.
+pub fn exits(round: &GameRound, free: &CellSet, offset: usize) -> usize {
+    Direction::ALL.into_iter().filter(|&dir| round.neighbour(offset, dir).is_some_and(|next| free.contains(next))).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn parity_limits_paths_through_plus_shapes() {
+        // Only the centre and its neighbours are free: the path can't get from one arm to another
+        let mut round = GameRound::new(0, 4, 4);
+        let centre = round.offset(1, 1);
+        let plus: Vec<_> = Direction::ALL.into_iter().filter_map(|dir| round.neighbour(centre, dir)).chain([centre]).collect();
+        for offset in 0..16 {
+            if !plus.contains(&offset) {
+                round.board.take(offset, 1);
+            }
+        }
+
+        let region = analysis::region(&round, centre);
+        assert_eq!(region.count(), 5);
+        assert_eq!(parity_bound(&round, &region, centre), 2);
+        assert_eq!(parity_bound(&round, &region, round.neighbour(centre, Direction::Up).unwrap()), 3);
+    }
+
+    #[test]
+    fn finds_paths_through_every_cell() {
+        let mut round = GameRound::new(0, 4, 4);
+        round.move_player(0, 0, 0);
+        let path = longest_path(&round, &Deadline::new(Duration::from_secs(10)));
+        assert!(path.exact);
+        assert_eq!(path.moves.len(), 15);
+
+        let mut cells = vec![round.offset(0, 0)];
+        for &dir in &path.moves {
+            let next = round.neighbour(*cells.last().unwrap(), dir).unwrap();
+            assert!(!cells.contains(&next));
+            cells.push(next);
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::fmt;
+use std::io;
+
+/// Why a session with the game server ended early. Anything in here is reason to reconnect,
+/// never to bring the bot down.
+#[derive(Debug)]
+pub enum ClientError {
+    /// Reading from or writing to the server failed.
+    Io(io::Error),
+    /// The server sent an error, e.g. because it didn't accept our login.
+    Server(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Io(err) => write!(f, "{err}"),
+            ClientError::Server(msg) => write!(f, "Game server sent error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Io(err) => Some(err),
+            ClientError::Server(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ClientError {
+    fn from(err: io::Error) -> ClientError {
+        ClientError::Io(err)
+    }
+}
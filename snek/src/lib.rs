@@ -0,0 +1,23 @@
+pub mod analysis;
+pub mod board;
+pub mod chamber;
+pub mod chat;
+pub mod client;
+pub mod collision;
+pub mod config;
+pub mod connection;
+pub mod deadline;
+pub mod endgame;
+pub mod error;
+pub mod logging;
+pub mod opponent;
+pub mod recorder;
+pub mod render;
+pub mod replay;
+pub mod round;
+pub mod runtime;
+pub mod stats;
+pub mod strategy;
+pub mod telemetry;
+
+pub use round::GameRound;
@@ -0,0 +1,62 @@
+//! Log output that says which bot it's about, for running several from one process. The name
+//! of the bot is kept per thread, and carried along by the tasks and threads of its session.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use env_logger::fmt::Formatter;
+
+thread_local! {
+    static BOT: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+}
+
+/// The bot the current thread is working for, if it was named.
+pub fn current() -> Option<Arc<str>> {
+    BOT.with(|bot| bot.borrow().clone())
+}
+
+/// Runs `f` with everything it logs attributed to `bot`.
+pub fn scoped<R>(bot: Option<Arc<str>>, f: impl FnOnce() -> R) -> R {
+    let outer = BOT.with(|current| current.replace(bot));
+    let result = f();
+    BOT.with(|current| current.replace(outer));
+    result
+}
+
+/// A future that logs as a bot, whichever worker thread polls it.
+pub struct Named<F> {
+    bot: Option<Arc<str>>,
+    inner: Pin<Box<F>>,
+}
+
+/// Wraps `inner` so everything it logs is attributed to `bot`.
+pub fn named<F: Future>(bot: Option<Arc<str>>, inner: F) -> Named<F> {
+    Named { bot, inner: Box::pin(inner) }
+}
+
+impl<F: Future> Future for Named<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<F::Output> {
+        let this = &mut *self;
+        scoped(this.bot.clone(), || this.inner.as_mut().poll(cx))
+    }
+}
+
+/// The format of `env_logger`, with the name of the bot in front of every line of the
+/// message, so that boards drawn by several bots can be told apart.
+pub fn format(buf: &mut Formatter, record: &log::Record) -> io::Result<()> {
+    let level = buf.default_level_style(record.level());
+    write!(buf, "[{} {level}{:<5}{level:#} {}] ", buf.timestamp(), record.level(), record.target())?;
+    let Some(bot) = current() else {
+        return writeln!(buf, "{}", record.args());
+    };
+    for line in record.args().to_string().split('\n') {
+        writeln!(buf, "{bot}: {line}")?;
+    }
+    Ok(())
+}
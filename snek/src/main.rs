@@ -1,315 +1,307 @@
-use std::io::BufReader;
-use std::io::prelude::*;
-use std::net::{Ipv6Addr, TcpStream};
-use std::{iter, thread};
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
 use std::time::Duration;
-use anyhow::{Result, bail, Context};
-
-
-// fn read_one_line(reader: &mut BufReader<TcpStream>) {
-//     let mut line = String::new();
-//     assert!(reader.read_line(&mut line).unwrap() > 0);
-//     println!("recv: {}", line);
-// }
-
-#[derive(Debug)]
-enum Message {
-    Motd(String),
-    Error(String),
-    Game { width: usize, height: usize, player_id: usize },
-    Pos { player_id: usize, x: usize, y: usize },
-    Tick,
-    Die(Vec<usize>),
-    Message { player_id: usize, msg: String },
-    Win { wins: usize, losses: usize },
-    Lose { wins: usize, losses: usize },
+use clap::{Parser, Subcommand};
+use snek::config::{self, Bot, Config, Profile, Verbosity};
+use snek::logging;
+use snek::render::Render;
+use snek::replay;
+use snek::runtime;
+use snek::stats;
+use snek::strategy::{self, Strategy};
+use snek_protocol::{Direction, Edges, HeadOn};
+
+/// A bot for gpn-tron.
+#[derive(Parser, Debug)]
+struct Args {
+    /// TOML profile to read settings from. Flags given here override it.
+    #[arg(long, short)]
+    config: Option<PathBuf>,
+
+    /// Host name or IP address (v4 or v6) of the game server
+    #[arg(long)]
+    host: Option<String>,
+
+    #[arg(long)]
+    port: Option<u16>,
+
+    #[arg(long)]
+    username: Option<String>,
+
+    #[arg(long)]
+    password: Option<String>,
+
+    #[arg(long, short)]
+    strategy: Option<String>,
+
+    /// Play as this bot, given as USERNAME or USERNAME:STRATEGY. Give it several times to play
+    /// several bots at once, these replace the bots of the profile
+    #[arg(long = "bot", value_parser = parse_bot)]
+    bots: Vec<Bot>,
+
+    /// Also print messages the bot doesn't act on
+    #[arg(long, short, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Only print round results and errors
+    #[arg(long, short)]
+    quiet: bool,
+
+    /// How to draw the board after each tick. Logged at debug level with the target snek::render
+    #[arg(long, value_enum)]
+    render: Option<Render>,
+
+    /// Seconds to wait before reconnecting, doubled for every failed attempt in a row
+    #[arg(long)]
+    reconnect_delay: Option<f64>,
+
+    /// Never wait longer than this many seconds before reconnecting
+    #[arg(long)]
+    max_reconnect_delay: Option<f64>,
+
+    /// Give up after this many failed connection attempts in a row
+    #[arg(long)]
+    max_reconnects: Option<u32>,
+
+    /// Milliseconds the strategy may think about a move
+    #[arg(long)]
+    tick_budget_ms: Option<u64>,
+
+    /// Milliseconds after which a fallback move is sent if the strategy hasn't decided yet
+    #[arg(long)]
+    fallback_after_ms: Option<u64>,
+
+    /// Directory to record sessions to
+    #[arg(long, conflicts_with = "no_record")]
+    record_dir: Option<PathBuf>,
+
+    /// Don't record sessions
+    #[arg(long)]
+    no_record: bool,
+
+    /// File to keep the result of every round in
+    #[arg(long, conflicts_with = "no_stats")]
+    stats_file: Option<PathBuf>,
+
+    /// Don't keep the results of rounds
+    #[arg(long)]
+    no_stats: bool,
+
+    /// Append a JSON line describing every decision to this file
+    #[arg(long)]
+    telemetry: Option<PathBuf>,
+
+    /// What happens at the edges of the map on the server: wrap or walls
+    #[arg(long)]
+    edges: Option<Edges>,
+
+    /// The server leaves the trails of dead players on the map
+    #[arg(long)]
+    keep_dead_trails: bool,
+
+    /// Who dies on the server when players move into the same cell: all-die or lowest-id-wins
+    #[arg(long)]
+    head_on: Option<HeadOn>,
+
+    #[command(subcommand)]
+    command: Option<Cmd>,
 }
 
-#[derive(Debug)]
-enum Direction {
-    Up,
-    Right,
-    Down,
-    Left,
-}
-
-impl Direction {
-    fn as_str(&self) -> &'static str {
-        match self {
-            Direction::Up => "up",
-            Direction::Right => "right",
-            Direction::Down => "down",
-            Direction::Left => "left",
-        }
-    }
-}
-
-impl Message {
-    fn from(s: String) -> Result<Message> {
-        let trimmed = s.trim();
-        let parts: Vec<_> = trimmed.split("|").collect();
-        match &parts.as_slice() {
-            ["motd", msg] => {
-                Ok(Message::Motd(msg.to_string()))
-            }
-
-            ["error", msg] => {
-                Ok(Message::Error(msg.to_string()))
-            }
-
-            ["game", width, height, player_id] => {
-                Ok(Message::Game {
-                    width: width.parse::<usize>().context("Game.width")?,
-                    height: height.parse::<usize>().context("Game.height")?,
-                    player_id: player_id.parse::<usize>().context("Game.player_id")?,
-                })
-            }
-
-            ["pos", id, x, y] => {
-                Ok(Message::Pos {
-                    player_id: id.parse::<usize>().context("Pos.player_id")?,
-                    x: x.parse::<usize>().context("Pos.x")?,
-                    y: y.parse::<usize>().context("Pos.y")?,
-                })
-            },
-
-            ["tick"] => {
-                Ok(Message::Tick)
-            }
-
-            ["die", players@ ..] => {
-                let list: Vec<_> = players.iter().map(|id| id.parse::<usize>().unwrap()).collect();
-                Ok(Message::Die(list))
-            }
-
-            ["message", player_id, msg] => {
-                let player_id = player_id.parse::<usize>().context("Message.player_id")?;
-                Ok(Message::Message { player_id, msg: msg.to_string()})
-            }
-
-            ["win", wins, losses] => {
-                Ok(Message::Win {
-                    wins: wins.parse::<usize>().context("Win.wins")?,
-                    losses: losses.parse::<usize>().context("Win.losses")?,
-                })
-            }
-
-            ["lose", wins, losses] => {
-                Ok(Message::Lose {
-                    wins: wins.parse::<usize>().context("Lose.wins")?,
-                    losses: losses.parse::<usize>().context("Lose.losses")?,
-                })
-            }
-
-            x => {
-                bail!("Failed to parse message '{}'", trimmed)
-            }
-        }
-
-    }
-}
-
-const MARKERS: &[char] = &['X', 'O', 'V', 'B'];
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// Connect to the game server and play (the default)
+    Play,
 
+    /// Run a recorded session through the strategy and compare its moves to the recorded ones
+    Replay { recording: PathBuf },
 
-#[derive(Debug)]
-struct GameRound {
-    width: usize,
-    height: usize,
-    player_id: usize,
-    alive_players: usize,
-    first_tick: bool,
-    player_state: Vec<Option<usize>>,
-    x: usize,
-    y: usize,
-    stream: Rc<RefCell<TcpStream>>,
+    /// Summarize the results of past rounds per strategy
+    Stats {
+        /// Also break the results down by day
+        #[arg(long)]
+        daily: bool,
+    },
 }
 
-impl GameRound {
-    pub fn new(stream: Rc<RefCell<TcpStream>>, player_id: usize, width: usize, height: usize) -> GameRound {
-        GameRound {
-            width,
-            height,
-            player_id,
-            alive_players: 0,
-            first_tick: true,
-            player_state: iter::repeat(None).take(width * height).collect(),
-            x: 0,
-            y: 0,
-            stream,
+impl Args {
+    fn into_config(self) -> anyhow::Result<(Config, Option<Cmd>)> {
+        let mut config = Config::default();
+        if let Some(path) = &self.config {
+            Profile::load(path)?.apply(&mut config)?;
         }
-    }
 
-    pub fn offset(&self, x: usize, y: usize) -> usize {
-        y * self.width + x
-    }
+        config.host = self.host.unwrap_or(config.host);
+        config.port = self.port.unwrap_or(config.port);
+        config.username = self.username.unwrap_or(config.username);
+        config.password = self.password.unwrap_or(config.password);
+        config.strategy = self.strategy.unwrap_or(config.strategy);
+        if !self.bots.is_empty() {
+            config.bots = self.bots;
+        }
+        if self.quiet {
+            config.verbosity = Verbosity::Quiet;
+        } else if self.verbose {
+            config.verbosity = Verbosity::Verbose;
+        }
+        config.render = self.render.unwrap_or(config.render);
+        if let Some(delay) = self.reconnect_delay {
+            config.reconnect.delay = config::seconds("--reconnect-delay", delay)?;
+        }
+        if let Some(max_delay) = self.max_reconnect_delay {
+            config.reconnect.max_delay = config::seconds("--max-reconnect-delay", max_delay)?;
+        }
+        if let Some(max_attempts) = self.max_reconnects {
+            config.reconnect.max_attempts = Some(max_attempts);
+        }
+        if let Some(budget) = self.tick_budget_ms {
+            config.tick_budget = Duration::from_millis(budget);
+        }
+        if let Some(fallback_after) = self.fallback_after_ms {
+            config.fallback_after = Duration::from_millis(fallback_after);
+        }
+        if self.no_record {
+            config.recordings = None;
+        } else if let Some(dir) = self.record_dir {
+            config.recordings = Some(dir);
+        }
+        if self.no_stats {
+            config.stats = None;
+        } else if let Some(path) = self.stats_file {
+            config.stats = Some(path);
+        }
+        if let Some(path) = self.telemetry {
+            config.telemetry = Some(path);
+        }
+        config.rules.edges = self.edges.unwrap_or(config.rules.edges);
+        if self.keep_dead_trails {
+            config.rules.clear_dead_trails = false;
+        }
+        config.rules.head_on = self.head_on.unwrap_or(config.rules.head_on);
+        config.check_timing()?;
 
-    pub fn next_offset(&self, mut x: usize, mut y: usize, dir: Direction) -> usize {
-        let mut x = x as i32;
-        let mut y = y as i32;
-        match dir {
-            Direction::Up => y -= 1,
-            Direction::Down => y += 1,
-            Direction::Right => x += 1,
-            Direction::Left => x -= 1,
-        };
-
-        let width = self.width as i32;
-        let height = self.height as i32;
-        x = ((x % width) + width) % width;
-        y = ((y % height) + height) % height;
-        self.offset(x as usize, y as usize)
+        Ok((config, self.command))
     }
+}
 
-    pub fn is_move_blocked(&self, mut x: usize, mut y: usize, dir: Direction) -> bool {
-        let next_offset = self.next_offset(x, y, dir);
-        self.player_state[next_offset].is_some()
+fn parse_bot(arg: &str) -> Result<Bot, String> {
+    let (username, strategy) = match arg.split_once(':') {
+        Some((username, strategy)) => (username, Some(strategy.to_string())),
+        None => (arg, None),
+    };
+    if username.is_empty() {
+        return Err("The username can't be empty".to_string());
     }
+    Ok(Bot { username: username.to_string(), password: None, strategy })
+}
 
-    pub fn send_move(&self, dir: Direction) {
-        let join_msg = format!("move|{}\n", dir.as_str());
-        self.stream.borrow_mut().write(join_msg.as_bytes()).expect("Failed to send join message");
-        println!("Moving {}!", dir.as_str());
+fn main() {
+    let (config, command) = Args::parse().into_config().unwrap_or_else(|err| {
+        eprintln!("{err:#}");
+        process::exit(1);
+    });
+
+    // The replay prints a report of its own, every tick of it would drown that out
+    let verbosity = match command {
+        Some(Cmd::Replay { .. }) => config.verbosity.min(Verbosity::Quiet),
+        _ => config.verbosity,
+    };
+    env_logger::Builder::new().filter_level(verbosity.level()).parse_default_env().format(logging::format).init();
+
+    match command {
+        Some(Cmd::Replay { recording }) => replay_file(&recording, load_strategy(&config.strategy).as_mut(), &config),
+        Some(Cmd::Stats { daily }) => print_stats(&config, daily),
+        Some(Cmd::Play) | None => play(&config),
     }
 }
 
+fn load_strategy(name: &str) -> Box<dyn Strategy> {
+    strategy::by_name(name).unwrap_or_else(|| {
+        log::error!("Unknown strategy '{name}', available: {}", strategy::NAMES.join(", "));
+        process::exit(1);
+    })
+}
 
-fn round_loop(reader: &mut BufReader<TcpStream>, mut info: GameRound) {
-    println!("\n\n\nStarting a new round. Player {}, Map: {}x{}", info.player_id, info.width, info.height);
-
-    loop {
-        let msg = read_next_message(reader);
-        match msg {
-            Message::Pos { player_id, x, y } => {
-                let offset = info.offset(x, y);
-                info.player_state[offset] = Some(player_id);
-
-                if player_id == info.player_id {
-                    info.x = x;
-                    info.y = y;
-                }
-
-                if info.first_tick {
-                    info.alive_players += 1;
-                }
-            }
-
-            Message::Tick => {
-                // In the first tick we need to collect information on who
-                // we are playing against, so it gets special handling.
-                info.first_tick = false;
-
-                if info.is_move_blocked(info.x, info.y, Direction::Up) {
-                    println!("Up is not free");
-                    if !info.is_move_blocked(info.x, info.y, Direction::Right) {
-                        info.send_move(Direction::Right);
-                    } else {
-                        info.send_move(Direction::Left);
-                    }
-                }
-
-                // if info.player_state[next_offset].is_some() {
-                //     info.send_move(Direction::Right);
-                //     println!("Moving right!");
-                // }
-
-                for y in 0..info.height  {
-                    for x in 0..info.width {
-                        let offset = y * info.width + x;
-                        if let Some(player_id) = info.player_state[offset] {
-                            let marker = MARKERS[player_id];
-                            print!("{} ", marker);
-                        } else {
-                            print!("  ");
-                        }
-                    }
-                    print!("\n")
-                }
-                println!("{}", "-".repeat(info.width*2));
-            }
-
-            Message::Die(ids) => {
-                info.alive_players -= ids.len();
-                println!("Players left alive: {}", info.alive_players);
-                for x in info.player_state.iter_mut() {
-                    if x.is_none() {
-                        continue;
-                    }
-
-                    let id = x.unwrap();
-                    if ids.contains(&id) {
-                        *x = None;
-                    }
-                }
-            }
-
-            Message::Win { .. } => {
-                println!("Won!");
-                return
-            }
-
-            Message::Lose { .. } => {
-                println!("Lost!");
-                break
-            }
-
-            Message::Message { .. } => { /* Don't care */ }
-
-            msg => {
-                println!("Unhandled message in round loop: {:?}", msg);
-            }
+/// Plays every bot of `config` at once, each with a reconnect loop of its own.
+fn play(config: &Config) {
+    let bots = config.per_bot();
+    // Better to learn about a typo before any of them connects
+    let strategies: Vec<_> = bots.iter().map(|bot| load_strategy(&bot.strategy)).collect();
+    let several = bots.len() > 1;
+
+    let async_runtime = tokio::runtime::Runtime::new().unwrap_or_else(|err| {
+        log::error!("Failed to start the async runtime: {err}");
+        process::exit(1);
+    });
+    async_runtime.block_on(async {
+        let players: Vec<_> = bots
+            .into_iter()
+            .zip(strategies)
+            .map(|(bot, strategy)| {
+                let name = several.then(|| Arc::from(bot.username.as_str()));
+                tokio::spawn(logging::named(name, runtime::play(bot, strategy)))
+            })
+            .collect();
+        // Each only returns when it gave up reconnecting
+        for player in players {
+            let _ = player.await;
         }
-    }
-
+    });
+    process::exit(1);
 }
 
-fn read_next_message(reader: &mut BufReader<TcpStream>) -> Message {
-    let mut line = String::new();
-    let size = reader.read_line(&mut line).expect("Couldn't read message from Game Server");
-    assert!(size > 0, "Connection to Game Server seems to have been lost");
-    Message::from(line.clone()).expect("Failed to parse message from Game Server")
-}
+fn replay_file(path: &Path, strategy: &mut dyn Strategy, config: &Config) {
+    let ticks = match replay::run(path, strategy, config) {
+        Ok(ticks) => ticks,
+        Err(err) => {
+            log::error!("Failed to replay {}: {err:#}", path.display());
+            process::exit(1);
+        }
+    };
+
+    let show = |dir: Option<Direction>| dir.map_or("-".to_string(), |d| d.to_string());
+    for tick in &ticks {
+        let marker = if tick.differs() { "  <-- differs" } else { "" };
+        println!(
+            "round {:>3} tick {:>4}: sent {:>5}, now {:>5}{marker}",
+            tick.round, tick.tick, show(tick.recorded), show(tick.replayed),
+        );
+    }
 
-fn main() {
-    loop {
-        println!("Attempting connection");
-        connect_loop();
-        println!("Connection closed, waiting for some time");
-        thread::sleep(Duration::from_secs(2));
+    let differing = ticks.iter().filter(|t| t.differs()).count();
+    println!("{differing} of {} moves differ from the recording", ticks.len());
+    if differing > 0 {
+        process::exit(1);
     }
 }
 
-fn connect_loop() {
-    let ip = "127.0.0.1:4000";
-    let mut stream = TcpStream::connect(ip)
-        .expect("Connection to game server failed");
-    let mut stream = Rc::new(RefCell::new(stream));
-
-    let mut reader = BufReader::new(stream.borrow().try_clone().unwrap());
-
-    loop {
-        let msg = read_next_message(&mut reader);
-        match msg {
-            Message::Game { width, height, player_id } => {
-                round_loop(&mut reader, GameRound::new(stream.clone(), player_id, width, height))
-            }
-
-            Message::Error(msg) => {
-                println!("Game Server sends error: {msg}");
-                return;
-            }
-
-            Message::Motd(msg) => {
-                println!("MOTD: {msg}");
-                let join_msg = "join|Snekisnek|jkasdfjkshdfjksdfkjhsdkjhfsdjk\n";
-                stream.borrow_mut().write(join_msg.as_bytes()).expect("Failed to send join message");
-            }
-
-            x => {
-                println!("ignoring: {:?}", x);
-            }
-        }
+fn print_stats(config: &Config, daily: bool) {
+    let Some(path) = &config.stats else {
+        log::error!("No stats file configured");
+        process::exit(1);
+    };
+    let records = stats::load(path).unwrap_or_else(|err| {
+        log::error!("Failed to read {}: {err}", path.display());
+        process::exit(1);
+    });
+
+    println!(
+        "{:<14} {:<20} {:>10} {:>10} {:>7} {:>7} {:>10} {:>10}  deaths",
+        "strategy", "version", "first", "last", "rounds", "win %", "avg ticks", "opponents",
+    );
+    for ((strategy, version, _), summary) in stats::summarize(&records, daily) {
+        let deaths: Vec<_> = summary.deaths.iter().map(|(cause, count)| format!("{cause}: {count}")).collect();
+        println!(
+            "{:<14} {:<20} {:>10} {:>10} {:>7} {:>7.1} {:>10.1} {:>10.1}  {}",
+            strategy,
+            version,
+            summary.first,
+            summary.last,
+            summary.rounds,
+            100.0 * summary.win_rate(),
+            summary.average_ticks(),
+            summary.average_opponents(),
+            deaths.join(", "),
+        );
     }
 }
@@ -0,0 +1,232 @@
+//! Guesses how each opponent picks their moves, from the positions the server sends. Many
+//! bots follow a simple rule, and once we know which one, we can predict their next move far
+//! better than by assuming they'll do whatever hurts us most.
+//!
+//! Every opponent is checked against a handful of [`Behaviour`]s. Each observed move makes
+//! the behaviours that would have made it more likely, Bayes style, and predictions mix the
+//! behaviours by how likely they are.
+
+use std::sync::Arc;
+
+use snek_protocol::Direction;
+
+use crate::GameRound;
+
+/// Share of moves a behaviour is assumed to get "wrong". Keeps one odd move from ruling a
+/// behaviour out for good.
+const NOISE: f64 = 0.1;
+
+/// Evidence is multiplied by this on every move, so the model follows opponents that change
+/// their ways, e.g. once they are cornered.
+const DECAY: f64 = 0.97;
+
+/// Simple ways to pick a move among the ones that don't run into a trail right away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Behaviour {
+    /// Any free move, picked uniformly.
+    Random,
+    /// Keeps going straight, turns right when blocked, and left only when it has to.
+    StraightRight,
+    /// Keeps going straight, turns left when blocked, and right only when it has to.
+    StraightLeft,
+    /// Moves to the free cell with the fewest free neighbours, i.e. along walls and trails.
+    WallHugger,
+    /// Moves to the free cell with the most free neighbours.
+    SpaceSeeker,
+}
+
+impl Behaviour {
+    pub const ALL: [Behaviour; 5] = [
+        Behaviour::Random,
+        Behaviour::StraightRight,
+        Behaviour::StraightLeft,
+        Behaviour::WallHugger,
+        Behaviour::SpaceSeeker,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Behaviour::Random => "random",
+            Behaviour::StraightRight => "straight, then right",
+            Behaviour::StraightLeft => "straight, then left",
+            Behaviour::WallHugger => "wall hugger",
+            Behaviour::SpaceSeeker => "space seeker",
+        }
+    }
+
+    /// The chance of each of `moves` under this behaviour, without noise. `ahead` is where
+    /// the player went last, `exits` the free neighbours of the cell each move leads to.
+    fn choices(self, moves: &[Direction], ahead: Option<Direction>, exits: &[usize]) -> Vec<f64> {
+        let uniform_among = |picked: &dyn Fn(usize) -> bool| {
+            let count = (0..moves.len()).filter(|&i| picked(i)).count().max(1);
+            (0..moves.len()).map(|i| if picked(i) { 1.0 / count as f64 } else { 0.0 }).collect()
+        };
+
+        let preferred = |turn: fn(Direction) -> Direction, other: fn(Direction) -> Direction| {
+            let Some(ahead) = ahead else {
+                return uniform_among(&|_| true);
+            };
+            let first = [ahead, turn(ahead), other(ahead)].into_iter().find(|dir| moves.contains(dir));
+            uniform_among(&|i| Some(moves[i]) == first)
+        };
+
+        match self {
+            Behaviour::Random => uniform_among(&|_| true),
+            Behaviour::StraightRight => preferred(Direction::turn_right, Direction::turn_left),
+            Behaviour::StraightLeft => preferred(Direction::turn_left, Direction::turn_right),
+            Behaviour::WallHugger => {
+                let fewest = exits.iter().copied().min().unwrap_or(0);
+                uniform_among(&|i| exits[i] == fewest)
+            }
+            Behaviour::SpaceSeeker => {
+                let most = exits.iter().copied().max().unwrap_or(0);
+                uniform_among(&|i| exits[i] == most)
+            }
+        }
+    }
+}
+
+/// What we learned about one opponent so far.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpponentModel {
+    /// Log likelihood of the moves seen so far under each behaviour, in the order of
+    /// [`Behaviour::ALL`].
+    evidence: [f64; Behaviour::ALL.len()],
+    /// Number of moves observed that told the behaviours apart at all.
+    pub moves_seen: usize,
+}
+
+impl Default for OpponentModel {
+    fn default() -> OpponentModel {
+        OpponentModel { evidence: [0.0; Behaviour::ALL.len()], moves_seen: 0 }
+    }
+}
+
+impl OpponentModel {
+    /// How likely each behaviour is, in the order of [`Behaviour::ALL`]. Uniform until we
+    /// saw a move.
+    pub fn posterior(&self) -> [f64; Behaviour::ALL.len()] {
+        let max = self.evidence.iter().copied().fold(f64::MIN, f64::max);
+        let mut weights = self.evidence.map(|e| (e - max).exp());
+        let total: f64 = weights.iter().sum();
+        for weight in &mut weights {
+            *weight /= total;
+        }
+        weights
+    }
+
+    /// The behaviour that explains the moves seen best, and how likely it is.
+    pub fn likeliest(&self) -> (Behaviour, f64) {
+        let posterior = self.posterior();
+        let best = (0..posterior.len()).max_by(|&a, &b| posterior[a].total_cmp(&posterior[b])).unwrap_or(0);
+        (Behaviour::ALL[best], posterior[best])
+    }
+}
+
+/// The moves of player `id` that don't run into a trail right away, with where the player
+/// went last and the free neighbours of the cell each move leads to.
+fn options(round: &GameRound, id: usize) -> Option<(Vec<Direction>, Option<Direction>, Vec<usize>)> {
+    let (x, y) = round.head(id)?;
+    let head = round.offset(x, y);
+    let moves: Vec<_> = Direction::ALL.into_iter().filter(|&dir| round.free_neighbour(head, dir).is_some()).collect();
+    let exits = moves
+        .iter()
+        .filter_map(|&dir| round.free_neighbour(head, dir))
+        .map(|next| Direction::ALL.iter().filter(|&&d| round.free_neighbour(next, d).is_some()).count())
+        .collect();
+    Some((moves, round.players[id].direction, exits))
+}
+
+/// Learns from player `id` moving to `(x, y)`, before the move is applied to `round`.
+/// Our own moves and first positions teach nothing and are ignored.
+pub fn observe(round: &mut GameRound, id: usize, x: usize, y: usize) {
+    if id == round.player_id {
+        return;
+    }
+    let Some((moves, ahead, exits)) = options(round, id) else { return };
+    let (hx, hy) = round.head(id).expect("Options exist only for players alive");
+    let Some(taken) = moves.iter().position(|&dir| round.next_offset(hx, hy, dir) == Some(round.offset(x, y))) else {
+        return;
+    };
+    // With one way to go, every behaviour goes there
+    if moves.len() < 2 {
+        return;
+    }
+
+    let models = Arc::make_mut(&mut round.opponents);
+    if models.len() <= id {
+        models.resize(id + 1, OpponentModel::default());
+    }
+    let model = &mut models[id];
+    for (evidence, behaviour) in model.evidence.iter_mut().zip(Behaviour::ALL) {
+        let chance = behaviour.choices(&moves, ahead, &exits)[taken];
+        *evidence = *evidence * DECAY + ((1.0 - NOISE) * chance + NOISE / moves.len() as f64).ln();
+    }
+    model.moves_seen += 1;
+}
+
+/// The chance of each move of player `id` that doesn't run into a trail right away, according
+/// to what we learned about them. Empty if they have no such move or aren't alive.
+pub fn predict(round: &GameRound, id: usize) -> Vec<(Direction, f64)> {
+    let Some((moves, ahead, exits)) = options(round, id) else { return vec![] };
+    let model = round.opponents.get(id).cloned().unwrap_or_default();
+
+    let mut chances = vec![NOISE / moves.len() as f64; moves.len()];
+    for (weight, behaviour) in model.posterior().into_iter().zip(Behaviour::ALL) {
+        for (chance, choice) in chances.iter_mut().zip(behaviour.choices(&moves, ahead, &exits)) {
+            *chance += (1.0 - NOISE) * weight * choice;
+        }
+    }
+    moves.into_iter().zip(chances).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays player 1 on an empty 12x12 map with `pick` for `ticks` ticks.
+    fn play(ticks: usize, pick: impl Fn(&GameRound) -> Direction) -> GameRound {
+        let mut round = GameRound::new(0, 12, 12);
+        round.move_player(0, 0, 0);
+        round.move_player(1, 6, 6);
+        round.move_player(1, 6, 5);
+        for _ in 0..ticks {
+            let dir = pick(&round);
+            let (x, y) = round.head(1).unwrap();
+            let (nx, ny) = round.position(round.next_offset(x, y, dir).unwrap());
+            observe(&mut round, 1, nx, ny);
+            round.move_player(1, nx, ny);
+        }
+        round
+    }
+
+    #[test]
+    fn recognizes_bots_that_go_straight_until_blocked() {
+        // Runs into its own trail every time it went around the map once
+        let round = play(40, |round| {
+            let ahead = round.players[1].direction.unwrap();
+            let (x, y) = round.head(1).unwrap();
+            [ahead, ahead.turn_right(), ahead.turn_left()]
+                .into_iter()
+                .find(|&dir| !round.is_move_blocked(x, y, dir))
+                .unwrap_or(ahead)
+        });
+        let (behaviour, chance) = round.opponents[1].likeliest();
+        assert_eq!(behaviour, Behaviour::StraightRight, "{:?}", round.opponents[1].posterior());
+        assert!(chance > 0.9);
+
+        let ahead = round.players[1].direction.unwrap();
+        let prediction = predict(&round, 1);
+        let straight = prediction.iter().find(|&&(dir, _)| dir == ahead).unwrap().1;
+        assert!(straight > 0.8, "{prediction:?}");
+        assert!((prediction.iter().map(|&(_, p)| p).sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn knows_nothing_before_seeing_moves() {
+        let round = play(0, |_| Direction::Up);
+        let prediction = predict(&round, 1);
+        assert_eq!(prediction.len(), 3);
+        assert!(prediction.iter().all(|&(_, p)| p > 0.2));
+    }
+}
@@ -0,0 +1,83 @@
+//! Writes the raw protocol traffic of a session to a file, so lost rounds can be looked at
+//! (and replayed) afterwards.
+//!
+//! Every line in a recording looks like `<ms since start>\t<round>\t<direction>\t<line>`, where
+//! direction is `<` for lines received from the server and `>` for lines we sent. Rounds are
+//! counted from 1 and start with the `game` line, everything before the first one is round 0.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use snek_protocol::Command;
+
+pub const RECEIVED: char = '<';
+pub const SENT: char = '>';
+
+pub struct Recorder {
+    file: LineWriter<File>,
+    path: PathBuf,
+    start: Instant,
+    round: usize,
+}
+
+impl Recorder {
+    /// Starts a new recording in `dir`, named after the current time. Bots connecting at the
+    /// same time get a number appended.
+    pub fn create(dir: &Path) -> io::Result<Recorder> {
+        fs::create_dir_all(dir)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let name = format!("session-{}-{:03}", now.as_secs(), now.subsec_millis());
+        let mut path = dir.join(format!("{name}.log"));
+        let mut taken = 0;
+        let file = loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    taken += 1;
+                    path = dir.join(format!("{name}-{taken}.log"));
+                }
+                result => break result?,
+            }
+        };
+        let mut file = LineWriter::new(file);
+        writeln!(file, "# snek session started at {} (unix time)", now.as_secs())?;
+
+        Ok(Recorder {
+            file,
+            path,
+            start: Instant::now(),
+            round: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn received(&mut self, line: &str) {
+        if line.starts_with("game|") {
+            self.round += 1;
+        }
+        self.write(RECEIVED, line);
+    }
+
+    pub fn sent(&mut self, line: &str) {
+        // Recordings get passed around, our password shouldn't be
+        if let Ok(Command::Join { username, .. }) = line.parse::<Command>() {
+            let redacted = Command::Join { username, password: "<redacted>".to_string() };
+            self.write(SENT, &redacted.to_string());
+        } else {
+            self.write(SENT, line);
+        }
+    }
+
+    fn write(&mut self, direction: char, line: &str) {
+        let millis = self.start.elapsed().as_millis();
+        let line = line.trim_end_matches(['\r', '\n']);
+        // Losing the recording is no reason to lose the round
+        if let Err(err) = writeln!(self.file, "{millis}\t{}\t{direction}\t{line}", self.round) {
+            log::warn!("Failed to write to {}: {err}", self.path.display());
+        }
+    }
+}
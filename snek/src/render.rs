@@ -0,0 +1,121 @@
+//! Draws the board to the terminal after each tick. Every cell is two characters wide, so
+//! the map comes out about as wide as it is high.
+
+use std::fmt::Write;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::GameRound;
+
+/// How to draw the board, if at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Render {
+    /// Don't draw the board, which saves time on big maps.
+    Off,
+    /// Letters only, for terminals and log files that don't do colours.
+    Plain,
+    /// A colour per player, with our own trail in bold.
+    Color,
+}
+
+/// 256-colour palette codes, picked to be told apart easily. Players beyond these
+/// reuse them, but keep a glyph of their own.
+const COLORS: &[u8] = &[196, 46, 33, 226, 201, 51, 208, 129, 118, 39, 220, 163];
+
+/// Marks the head of a player, next to their glyph.
+const HEAD: char = '@';
+
+/// The glyph trails of player `id` are drawn with: digits, then letters, then it starts over.
+fn glyph(id: usize) -> char {
+    const GLYPHS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    GLYPHS[id % GLYPHS.len()] as char
+}
+
+/// The escape sequence starting player `id`'s colour, bold for us.
+fn color(round: &GameRound, id: usize) -> String {
+    let bold = if id == round.player_id { "1;" } else { "" };
+    format!("\x1b[{bold}38;5;{}m", COLORS[id % COLORS.len()])
+}
+
+const RESET: &str = "\x1b[0m";
+
+/// The board, followed by a line per player saying who is who. Empty if `style` is
+/// [`Render::Off`].
+pub fn render(round: &GameRound, style: Render) -> String {
+    let mut out = String::new();
+    if style == Render::Off {
+        return out;
+    }
+
+    let paint = |out: &mut String, id: usize, text: &str| match style {
+        Render::Color => {
+            let _ = write!(out, "{}{text}{RESET}", color(round, id));
+        }
+        _ => out.push_str(text),
+    };
+
+    let heads: Vec<_> = round.head_offsets().collect();
+    for y in 0..round.height {
+        for x in 0..round.width {
+            let offset = round.offset(x, y);
+            let Some(id) = round.board.owner(offset) else {
+                out.push_str("  ");
+                continue;
+            };
+            let head = if heads.contains(&(id, offset)) { HEAD } else { ' ' };
+            paint(&mut out, id, &format!("{}{head}", glyph(id)));
+        }
+        out.push('\n');
+    }
+    out.push_str(&"-".repeat(round.width * 2));
+    out.push('\n');
+
+    for (id, player) in round.players.iter().enumerate() {
+        if player.head.is_none() {
+            continue;
+        }
+        paint(&mut out, id, &format!("{}{HEAD}", glyph(id)));
+        let _ = write!(out, " player {id}");
+        if id == round.player_id {
+            out.push_str(" (us)");
+        }
+        match player.alive {
+            true => {
+                let _ = writeln!(out, ", {} cells", player.trail_length);
+            }
+            false => out.push_str(", dead\n"),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_any_number_of_players() {
+        let mut round = GameRound::new(3, 40, 2);
+        for id in 0..40 {
+            round.move_player(id, id, 0);
+        }
+        round.move_player(3, 3, 1);
+        round.remove_players(&[7]);
+
+        let plain = render(&round, Render::Plain);
+        let lines: Vec<_> = plain.lines().collect();
+        assert!(lines[0].starts_with("0@1@2@3 4@5@6@  8@"));
+        assert!(lines[1].starts_with("      3@"));
+        assert_eq!(lines[3], "0@ player 0, 1 cells");
+        assert_eq!(lines[6], "3@ player 3 (us), 2 cells");
+        assert_eq!(lines[10], "7@ player 7, dead");
+        assert_eq!(lines.len(), 3 + 40);
+        assert!(!plain.contains('\x1b'));
+
+        let color = render(&round, Render::Color);
+        assert!(color.contains("\x1b[1;38;5;226m3@\x1b[0m"));
+        assert!(render(&round, Render::Off).is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+//! Feeds a session written by the [`Recorder`](crate::recorder::Recorder) back through the
+//! client, so we can see which moves the current code would make in a game that was already played.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use snek_protocol::{Command, Direction, Message};
+
+use crate::client;
+use crate::config::Config;
+use crate::connection::Transport;
+use crate::error::ClientError;
+use crate::recorder::{RECEIVED, SENT};
+use crate::strategy::Strategy;
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Received { round: usize, line: String },
+    Sent { line: String },
+}
+
+/// What happened on one tick of the recording, and what happens now.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TickReport {
+    pub round: usize,
+    /// Counted from 1 within the round.
+    pub tick: usize,
+    /// The move sent back when the session was recorded, if any.
+    pub recorded: Option<Direction>,
+    /// The move the strategy makes now, if any.
+    pub replayed: Option<Direction>,
+}
+
+impl TickReport {
+    pub fn differs(&self) -> bool {
+        self.recorded != self.replayed
+    }
+}
+
+/// A [`Transport`] reading from a recording instead of a server.
+pub struct Replay {
+    entries: VecDeque<Entry>,
+    round: usize,
+    tick: usize,
+    ticks: Vec<TickReport>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Replay> {
+        let contents = fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+        Replay::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Replay> {
+        let mut entries = VecDeque::new();
+        for (number, line) in contents.lines().enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<_> = line.splitn(4, '\t').collect();
+            let [_millis, round, direction, line] = fields.as_slice() else {
+                bail!("Line {} of the recording is malformed", number + 1);
+            };
+
+            let round = round.parse().with_context(|| format!("Line {}: round", number + 1))?;
+            let line = line.to_string();
+            match direction.chars().next() {
+                Some(RECEIVED) => entries.push_back(Entry::Received { round, line }),
+                Some(SENT) => entries.push_back(Entry::Sent { line }),
+                _ => bail!("Line {}: unknown direction '{direction}'", number + 1),
+            }
+        }
+
+        Ok(Replay {
+            entries,
+            round: 0,
+            tick: 0,
+            ticks: vec![],
+        })
+    }
+
+    /// Every tick seen so far.
+    pub fn ticks(&self) -> &[TickReport] {
+        &self.ticks
+    }
+
+    /// The last move we sent in answer to the tick just read, which is the one the server used.
+    fn recorded_move(&mut self) -> Option<Direction> {
+        let mut dir = None;
+        while let Some(Entry::Sent { line }) = self.entries.front() {
+            if let Ok(Command::Move(sent)) = line.parse::<Command>() {
+                dir = Some(sent);
+            }
+            self.entries.pop_front();
+        }
+        dir
+    }
+}
+
+impl Transport for Replay {
+    fn read_message(&mut self) -> Result<Option<Message>, ClientError> {
+        loop {
+            let Some(entry) = self.entries.pop_front() else { return Ok(None) };
+            let (round, line) = match entry {
+                Entry::Received { round, line } => (round, line),
+                // Whatever we sent outside of a tick doesn't matter for the comparison
+                Entry::Sent { .. } => continue,
+            };
+
+            let msg = match line.parse::<Message>() {
+                Ok(msg) => msg,
+                Err(err) => {
+                    log::warn!("Skipping recorded line: {err}");
+                    continue;
+                }
+            };
+
+            match msg {
+                Message::Game { .. } => {
+                    self.round = round;
+                    self.tick = 0;
+                }
+                Message::Tick => {
+                    self.tick += 1;
+                    let recorded = self.recorded_move();
+                    self.ticks.push(TickReport { round: self.round, tick: self.tick, recorded, replayed: None });
+                }
+                _ => {}
+            }
+
+            return Ok(Some(msg));
+        }
+    }
+
+    fn send(&mut self, cmd: &Command) -> Result<(), ClientError> {
+        if let (Command::Move(dir), Some(report)) = (cmd, self.ticks.last_mut()) {
+            report.replayed = Some(*dir);
+        }
+        Ok(())
+    }
+}
+
+/// Plays the whole recording with `strategy` and reports every tick. Only the timing,
+/// rendering and telemetry settings of `config` are used.
+pub fn run(path: &Path, strategy: &mut dyn Strategy, config: &Config) -> Result<Vec<TickReport>> {
+    let mut replay = Replay::load(path)?;
+    // Nothing we send goes anywhere, so the credentials don't matter
+    let join = Command::Join { username: String::new(), password: String::new() };
+    // The rounds were booked when they were played
+    let config = Config { stats: None, ..config.clone() };
+    // Reading a recording can't fail, this can only be an error the server sent back then
+    if let Err(err) = client::session_loop(&mut replay, &join, strategy, &config) {
+        log::warn!("Recording ends with: {err}");
+    }
+    Ok(replay.ticks)
+}
@@ -0,0 +1,331 @@
+use std::sync::Arc;
+
+use snek_protocol::{Direction, Rules};
+
+use crate::board::{Board, Checkpoint};
+use crate::opponent::OpponentModel;
+
+/// What we know about one player, us included, from the positions the server sent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Player {
+    /// Where the player is now. Stays at the last position once they're dead.
+    pub head: Option<(usize, usize)>,
+    /// Where the player was the tick before.
+    pub previous_head: Option<(usize, usize)>,
+    /// Which way the player went on their last move, if we saw two positions in a row.
+    pub direction: Option<Direction>,
+    /// Number of cells of the player's trail on the map, head included. Trails of dead players
+    /// only count if they are still on the map.
+    pub trail_length: usize,
+    pub alive: bool,
+}
+
+/// A point to [`GameRound::undo`] back to.
+#[derive(Debug, Clone)]
+pub struct RoundCheckpoint {
+    board: Checkpoint,
+    players: Vec<Player>,
+    alive_players: usize,
+    x: usize,
+    y: usize,
+    direction: Direction,
+}
+
+/// Everything we know about the round that is currently being played.
+#[derive(Debug, Clone)]
+pub struct GameRound {
+    pub width: usize,
+    pub height: usize,
+    pub rules: Rules,
+    pub player_id: usize,
+    pub alive_players: usize,
+    pub first_tick: bool,
+    /// Number of ticks so far, counted from 1.
+    pub tick: usize,
+    pub board: Board,
+    pub x: usize,
+    pub y: usize,
+    /// Every player we got a position for, indexed by player id.
+    pub players: Vec<Player>,
+    /// The direction we last sent to the server, i.e. the one we keep going in
+    /// if we don't send anything.
+    pub direction: Direction,
+    /// How each opponent seems to pick their moves, indexed by player id. Shared between
+    /// copies, since looking ahead doesn't teach us anything about them.
+    pub opponents: Arc<Vec<OpponentModel>>,
+}
+
+impl GameRound {
+    /// A round played by the rules of gpn-tron.
+    pub fn new(player_id: usize, width: usize, height: usize) -> GameRound {
+        GameRound::with_rules(player_id, width, height, Rules::default())
+    }
+
+    pub fn with_rules(player_id: usize, width: usize, height: usize, rules: Rules) -> GameRound {
+        GameRound {
+            width,
+            height,
+            rules,
+            player_id,
+            alive_players: 0,
+            first_tick: true,
+            tick: 0,
+            board: Board::new(width, height, rules.edges),
+            x: 0,
+            y: 0,
+            players: vec![],
+            direction: Direction::Up,
+            opponents: Arc::default(),
+        }
+    }
+
+    pub fn offset(&self, x: usize, y: usize) -> usize {
+        self.board.offset(x, y)
+    }
+
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        self.board.position(offset)
+    }
+
+    /// The cell next to `offset` in `dir`, `None` if that's over a wall.
+    pub fn neighbour(&self, offset: usize, dir: Direction) -> Option<usize> {
+        self.board.neighbour(offset, dir)
+    }
+
+    /// The cell next to `offset` in `dir`, if it's on the map and free.
+    pub fn free_neighbour(&self, offset: usize, dir: Direction) -> Option<usize> {
+        self.neighbour(offset, dir).filter(|&next| self.is_free(next))
+    }
+
+    pub fn is_free(&self, offset: usize) -> bool {
+        self.board.is_free(offset)
+    }
+
+    /// The head of player `id`, if they are alive.
+    pub fn head(&self, id: usize) -> Option<(usize, usize)> {
+        self.players.get(id).filter(|p| p.alive).and_then(|p| p.head)
+    }
+
+    /// `(player_id, offset)` of the head of every player still alive, including us.
+    pub fn head_offsets(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..self.players.len()).filter_map(|id| self.head(id).map(|(x, y)| (id, self.offset(x, y))))
+    }
+
+    /// Player `id` is now at `(x, y)`, as told by a `pos` message. Players are alive from
+    /// their first position on.
+    pub fn move_player(&mut self, id: usize, x: usize, y: usize) {
+        let offset = self.offset(x, y);
+        self.board.take(offset, id);
+        if id == self.player_id {
+            self.x = x;
+            self.y = y;
+        }
+
+        if self.players.len() <= id {
+            self.players.resize(id + 1, Player::default());
+        }
+        let previous = self.players[id].head;
+        let direction = previous.and_then(|(px, py)| Direction::ALL.into_iter().find(|&dir| self.next_offset(px, py, dir) == Some(offset)));
+
+        let player = &mut self.players[id];
+        player.previous_head = previous;
+        player.head = Some((x, y));
+        player.direction = direction;
+        player.trail_length += 1;
+        if !player.alive {
+            player.alive = true;
+            self.alive_players += 1;
+        }
+    }
+
+    /// The players `ids` died, as told by a `die` message. Their trails disappear from the
+    /// map, if the rules say so.
+    pub fn remove_players(&mut self, ids: &[usize]) {
+        let clear = self.rules.clear_dead_trails;
+        for &id in ids {
+            if let Some(player) = self.players.get_mut(id).filter(|p| p.alive) {
+                player.alive = false;
+                if clear {
+                    player.trail_length = 0;
+                }
+                self.alive_players = self.alive_players.saturating_sub(1);
+            }
+            if clear {
+                self.board.clear_player(id);
+            }
+        }
+    }
+
+    /// The cell one step from `(x, y)` in `dir`, `None` if that's over a wall.
+    pub fn next_offset(&self, x: usize, y: usize, dir: Direction) -> Option<usize> {
+        self.board.next_offset(x, y, dir)
+    }
+
+    pub fn is_move_blocked(&self, x: usize, y: usize, dir: Direction) -> bool {
+        self.next_offset(x, y, dir).is_none_or(|next| !self.is_free(next))
+    }
+
+    /// Starts remembering changes, so [`GameRound::step`]s can be undone back to this point.
+    pub fn checkpoint(&mut self) -> RoundCheckpoint {
+        RoundCheckpoint {
+            board: self.board.checkpoint(),
+            players: self.players.clone(),
+            alive_players: self.alive_players,
+            x: self.x,
+            y: self.y,
+            direction: self.direction,
+        }
+    }
+
+    /// Undoes every move and death since `checkpoint`, which can be undone back to again.
+    pub fn undo(&mut self, checkpoint: &RoundCheckpoint) {
+        self.board.undo(checkpoint.board);
+        self.players.clone_from(&checkpoint.players);
+        self.alive_players = checkpoint.alive_players;
+        self.x = checkpoint.x;
+        self.y = checkpoint.y;
+        self.direction = checkpoint.direction;
+    }
+
+    /// Moves the given `(player_id, direction)` pairs one cell at the same time, following
+    /// the [`Rules`], and returns the ids of the players that died doing so. A player dies
+    /// when moving over a wall or into any trail, and maybe when moving into the same cell as
+    /// another player. Players without a move stay where they are.
+    ///
+    /// This is meant for looking ahead on a copy of the round, the client itself only ever
+    /// learns about moves from the server.
+    pub fn step(&mut self, moves: &[(usize, Direction)]) -> Vec<usize> {
+        let mut died = vec![];
+        let targets: Vec<(usize, Direction, usize)> = moves
+            .iter()
+            .filter_map(|&(id, dir)| {
+                let (x, y) = self.head(id)?;
+                let Some(target) = self.next_offset(x, y, dir) else {
+                    died.push(id);
+                    return None;
+                };
+                Some((id, dir, target))
+            })
+            .collect();
+
+        died.extend(
+            targets
+                .iter()
+                .filter(|&&(id, _, target)| {
+                    let contenders = targets.iter().filter(|&&(_, _, t)| t == target).map(|&(other, _, _)| other);
+                    let head_on = contenders.clone().count() > 1 && self.rules.head_on.survivor(contenders) != Some(id);
+                    !self.is_free(target) || head_on
+                })
+                .map(|&(id, _, _)| id),
+        );
+
+        for &(id, dir, target) in &targets {
+            if died.contains(&id) {
+                continue;
+            }
+
+            let (x, y) = self.position(target);
+            self.move_player(id, x, y);
+            if id == self.player_id {
+                self.direction = dir;
+            }
+        }
+
+        if !died.is_empty() {
+            self.remove_players(&died);
+        }
+
+        died
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use snek_protocol::{Edges, HeadOn};
+
+    use super::*;
+
+    /// A round on a `width` x `height` map with players standing at `heads`, we are player 0.
+    fn round_with(width: usize, height: usize, heads: &[(usize, usize)]) -> GameRound {
+        round_with_rules(width, height, heads, Rules::default())
+    }
+
+    fn round_with_rules(width: usize, height: usize, heads: &[(usize, usize)], rules: Rules) -> GameRound {
+        let mut round = GameRound::with_rules(0, width, height, rules);
+        for (id, &(x, y)) in heads.iter().enumerate() {
+            round.move_player(id, x, y);
+        }
+        round
+    }
+
+    #[test]
+    fn step_moves_and_leaves_trails() {
+        let mut round = round_with(5, 5, &[(0, 0), (3, 3)]);
+        let died = round.step(&[(0, Direction::Up), (1, Direction::Right)]);
+
+        assert!(died.is_empty());
+        assert_eq!((round.x, round.y), (0, 4));
+        assert_eq!(round.direction, Direction::Up);
+        assert_eq!(round.head(0), Some((0, 4)));
+        assert_eq!(round.head(1), Some((4, 3)));
+        assert_eq!(round.players[1].previous_head, Some((3, 3)));
+        assert_eq!(round.players[1].direction, Some(Direction::Right));
+        assert_eq!(round.players[1].trail_length, 2);
+        assert_eq!(round.board.owner(round.offset(0, 0)), Some(0));
+        assert_eq!(round.board.owner(round.offset(4, 3)), Some(1));
+    }
+
+    #[test]
+    fn step_kills_on_trails_and_head_on() {
+        let mut round = round_with(5, 5, &[(0, 0), (2, 0), (1, 3)]);
+        let trail = round.offset(1, 2);
+        round.board.take(trail, 2);
+
+        // 0 and 1 meet at (1, 0), 2 runs into its own trail
+        let mut died = round.step(&[(0, Direction::Right), (1, Direction::Left), (2, Direction::Up)]);
+        died.sort();
+
+        assert_eq!(died, vec![0, 1, 2]);
+        assert_eq!(round.alive_players, 0);
+        assert!(round.players.iter().all(|p| !p.alive && p.trail_length == 0));
+        assert!(round.board.occupied().is_empty());
+    }
+
+    #[test]
+    fn step_follows_the_rules() {
+        let rules = Rules { edges: Edges::Walls, clear_dead_trails: false, head_on: HeadOn::LowestIdWins };
+        let mut round = round_with_rules(5, 5, &[(0, 0), (2, 0), (4, 4)], rules);
+
+        // 0 and 1 meet at (1, 0) and 0 gets it, 2 runs into the wall
+        let mut died = round.step(&[(0, Direction::Right), (1, Direction::Left), (2, Direction::Down)]);
+        died.sort();
+
+        assert_eq!(died, vec![1, 2]);
+        assert_eq!(round.head(0), Some((1, 0)));
+        assert_eq!(round.board.owner(round.offset(2, 0)), Some(1));
+        assert_eq!(round.board.owner(round.offset(4, 4)), Some(2));
+        assert_eq!(round.players[1].trail_length, 1);
+        assert!(round.is_move_blocked(1, 0, Direction::Up));
+    }
+
+    #[test]
+    fn undo_goes_back_to_the_checkpoint() {
+        let mut round = round_with(5, 5, &[(0, 0), (2, 0), (1, 3)]);
+        let before = round.clone();
+        let checkpoint = round.checkpoint();
+
+        for _ in 0..2 {
+            // 0 and 1 die head-on and their trails are cleared, 2 moves on
+            let died = round.step(&[(0, Direction::Right), (1, Direction::Left), (2, Direction::Up)]);
+            assert_eq!(died.len(), 2);
+            round.undo(&checkpoint);
+
+            assert_eq!(round.players, before.players);
+            assert_eq!(round.alive_players, 3);
+            assert_eq!(round.board.occupied(), before.board.occupied());
+            for id in 0..3 {
+                assert_eq!(round.board.trail(id), before.board.trail(id));
+            }
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! The client on top of tokio. Reading from the server, writing to it and playing each run
+//! as a task of their own, connected by channels. The socket is served while the strategy
+//! thinks, and one process can hold as many connections as it likes.
+//!
+//! Playing is still [`client::session_loop`], run on tokio's blocking pool, to which the
+//! channels are just another [`Transport`]. Every task of a session logs as the bot it plays
+//! for, see [`logging`].
+
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use snek_protocol::{Command, Message};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task;
+
+use crate::client;
+use crate::config::Config;
+use crate::connection::Transport;
+use crate::error::ClientError;
+use crate::logging;
+use crate::recorder::Recorder;
+use crate::strategy::Strategy;
+
+/// Messages the reader may get ahead of the strategy before it waits for it to catch up.
+const MESSAGE_BUFFER: usize = 1024;
+
+/// Commands waiting for the writer before the strategy waits for the socket.
+const COMMAND_BUFFER: usize = 64;
+
+/// The reader and the writer both write to the recording.
+type SharedRecorder = Option<Arc<Mutex<Recorder>>>;
+
+/// The strategy's end of the channels to the reader and writer tasks.
+struct Channels {
+    messages: mpsc::Receiver<Result<Message, ClientError>>,
+    commands: mpsc::Sender<Command>,
+}
+
+impl Transport for Channels {
+    fn read_message(&mut self) -> Result<Option<Message>, ClientError> {
+        self.messages.blocking_recv().transpose()
+    }
+
+    fn send(&mut self, cmd: &Command) -> Result<(), ClientError> {
+        self.commands.blocking_send(cmd.clone()).map_err(|_| {
+            // The writer only stops early when writing failed, the session reports why
+            io::Error::new(io::ErrorKind::BrokenPipe, "Connection to the game server is closed").into()
+        })
+    }
+}
+
+/// Passes every message from the server on until it closes the connection, or the strategy
+/// stops listening. Like [`Connection`](crate::connection::Connection), skips over lines that
+/// aren't valid messages.
+async fn read_messages(reader: OwnedReadHalf, recorder: SharedRecorder, messages: mpsc::Sender<Result<Message, ClientError>>) {
+    let mut reader = BufReader::new(reader);
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line).await {
+            // The server closed the connection
+            Ok(0) => return,
+            Ok(_) => {}
+            Err(err) => {
+                let _ = messages.send(Err(err.into())).await;
+                return;
+            }
+        }
+        if let Some(recorder) = &recorder {
+            recorder.lock().expect("Recorder lock poisoned").received(&line);
+        }
+
+        match line.parse::<Message>() {
+            Ok(msg) => {
+                if messages.send(Ok(msg)).await.is_err() {
+                    return;
+                }
+            }
+            Err(err) => log::warn!("Skipping message from game server: {err}"),
+        }
+    }
+}
+
+/// Writes every command to the server until the strategy is done sending.
+async fn write_commands(mut writer: OwnedWriteHalf, recorder: SharedRecorder, mut commands: mpsc::Receiver<Command>) -> io::Result<()> {
+    while let Some(cmd) = commands.recv().await {
+        let line = format!("{cmd}\n");
+        if let Some(recorder) = &recorder {
+            recorder.lock().expect("Recorder lock poisoned").sent(&line);
+        }
+        writer.write_all(line.as_bytes()).await?;
+    }
+    writer.shutdown().await
+}
+
+/// Connects to the server in `config` and plays until the connection ends. Hands the strategy
+/// back, so it can keep what it learned for the next session.
+pub async fn session(config: Arc<Config>, strategy: Box<dyn Strategy>) -> (Box<dyn Strategy>, Result<(), ClientError>) {
+    let stream = match connect(&config).await {
+        Ok(stream) => stream,
+        Err(err) => return (strategy, Err(err)),
+    };
+    let recorder = config.recordings.as_deref().and_then(|dir| match Recorder::create(dir) {
+        Ok(recorder) => {
+            log::info!("Recording session to {}", recorder.path().display());
+            Some(Arc::new(Mutex::new(recorder)))
+        }
+        Err(err) => {
+            log::warn!("Not recording this session: {err}");
+            None
+        }
+    });
+
+    let (reader, writer) = stream.into_split();
+    let (message_tx, messages) = mpsc::channel(MESSAGE_BUFFER);
+    let (commands, command_rx) = mpsc::channel(COMMAND_BUFFER);
+    let reader = tokio::spawn(logging::named(logging::current(), read_messages(reader, recorder.clone(), message_tx)));
+    let writer = tokio::spawn(logging::named(logging::current(), write_commands(writer, recorder, command_rx)));
+
+    let mut channels = Channels { messages, commands };
+    let join = Command::Join { username: config.username.clone(), password: config.password.clone() };
+    let bot = logging::current();
+    let playing = task::spawn_blocking(move || {
+        logging::scoped(bot, || {
+            let mut strategy = strategy;
+            let result = client::session_loop(&mut channels, &join, strategy.as_mut(), &config);
+            (strategy, result)
+        })
+    });
+    let (strategy, result) = playing.await.unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()));
+
+    // The server may keep the connection open after an error, we're done with it either way
+    reader.abort();
+    match writer.await {
+        // Anything the strategy noticed is a consequence of this
+        Ok(Err(err)) => (strategy, Err(err.into())),
+        _ => (strategy, result),
+    }
+}
+
+async fn connect(config: &Config) -> Result<TcpStream, ClientError> {
+    let addrs = config.server_addrs()?;
+    Ok(TcpStream::connect(addrs.as_slice()).await?)
+}
+
+/// Plays on the server in `config`, reconnecting whenever the connection ends. Only returns
+/// once the [`ReconnectPolicy`](crate::config::ReconnectPolicy) says to give up.
+pub async fn play(config: Config, mut strategy: Box<dyn Strategy>) -> ClientError {
+    let config = Arc::new(config);
+    let mut failed_attempts = 0;
+    loop {
+        log::info!("Attempting connection to {}:{}", config.host, config.port);
+        let result;
+        (strategy, result) = session(config.clone(), strategy).await;
+        match result {
+            Ok(()) => {
+                failed_attempts = 0;
+                log::info!("Connection closed");
+            }
+            Err(err) => {
+                failed_attempts += 1;
+                log::error!("Connection to game server failed: {err}");
+                if config.reconnect.max_attempts.is_some_and(|max| failed_attempts >= max) {
+                    log::error!("Giving up after {failed_attempts} failed attempts");
+                    return err;
+                }
+            }
+        }
+
+        let delay = config.reconnect.backoff(failed_attempts, &mut rand::thread_rng());
+        log::info!("Reconnecting in {:.1}s", delay.as_secs_f64());
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::strategy;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn plays_a_round_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+
+            writer.write_all(b"motd|hello\n").await.unwrap();
+            let join = lines.next_line().await.unwrap().unwrap();
+            writer.write_all(b"game|4|4|0\npos|0|1|1\ntick\n").await.unwrap();
+            let moved = lines.next_line().await.unwrap().unwrap();
+            writer.write_all(b"lose|0|1\n").await.unwrap();
+            (join, moved)
+        });
+
+        let config = Config {
+            port,
+            username: "snek".to_string(),
+            password: "secret".to_string(),
+            recordings: None,
+            stats: None,
+            ..Config::default()
+        };
+        let (_, result) = session(Arc::new(config), strategy::by_name("straight").unwrap()).await;
+        result.unwrap();
+
+        let (join, moved) = server.await.unwrap();
+        assert_eq!(join, "join|snek|secret");
+        assert!(moved.starts_with("move|"), "{moved}");
+    }
+}
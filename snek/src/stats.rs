@@ -0,0 +1,231 @@
+//! Keeps the result of every round we play in a JSON lines file, so we can tell how strategies
+//! (and versions of them) do over time. The server only keeps a running win/loss count.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::GameRound;
+
+/// Version of the bot, stored with every round so changes to a strategy can be told apart.
+/// The package version plus the commit it was built from, `-dirty` if it had changes on top.
+pub const VERSION: &str = env!("SNEK_VERSION");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Won,
+    Lost,
+}
+
+/// What we ran into on the tick we died.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Death {
+    /// A trail of our own.
+    OwnTrail,
+    OpponentTrail,
+    /// The edge of the map, on maps with walls.
+    Wall,
+    /// The cell was free, but someone else moved into it at the same time.
+    HeadOn,
+}
+
+/// One round as we saw it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoundRecord {
+    /// Milliseconds since the unix epoch, at the end of the round.
+    pub time_ms: u64,
+    pub username: String,
+    pub strategy: String,
+    pub version: String,
+    pub width: usize,
+    pub height: usize,
+    pub opponents: usize,
+    pub outcome: Outcome,
+    /// Ticks we were alive for.
+    pub ticks: usize,
+    /// `None` if we won, or lost without dying, e.g. by timing out.
+    pub death: Option<Death>,
+}
+
+impl RoundRecord {
+    /// The record of `round` ending now, with `death` if we died in it.
+    pub fn new(round: &GameRound, username: &str, strategy: &str, outcome: Outcome, death: Option<Death>) -> RoundRecord {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        RoundRecord {
+            time_ms: now.as_millis() as u64,
+            username: username.to_string(),
+            strategy: strategy.to_string(),
+            version: VERSION.to_string(),
+            width: round.width,
+            height: round.height,
+            opponents: round.players.len().saturating_sub(1),
+            outcome,
+            ticks: round.tick,
+            death,
+        }
+    }
+
+    /// The day the round was played, as `YYYY-MM-DD` in UTC.
+    pub fn date(&self) -> String {
+        civil_date(self.time_ms / 1000 / 86_400)
+    }
+}
+
+/// Why we died, judging from the round as it was before the `die` message, i.e. the one
+/// we made our last move in.
+pub fn cause_of_death(round: &GameRound) -> Death {
+    let Some(target) = round.next_offset(round.x, round.y, round.direction) else {
+        return Death::Wall;
+    };
+    match round.board.owner(target) {
+        Some(id) if id == round.player_id => Death::OwnTrail,
+        Some(_) => Death::OpponentTrail,
+        None => Death::HeadOn,
+    }
+}
+
+/// Appends `record` to the store at `path`, creating it if needed.
+pub fn append(path: &Path, record: &RoundRecord) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(line.as_bytes())
+}
+
+/// Every record in the store at `path`, in the order they were played. Lines that can't be
+/// read are skipped with a warning, so one broken write doesn't cost us the history.
+pub fn load(path: &Path) -> io::Result<Vec<RoundRecord>> {
+    let contents = fs::read_to_string(path)?;
+    let mut records = vec![];
+    for (number, line) in contents.lines().enumerate().filter(|(_, line)| !line.is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(record) => records.push(record),
+            Err(err) => log::warn!("Skipping line {} of {}: {err}", number + 1, path.display()),
+        }
+    }
+    Ok(records)
+}
+
+/// How a group of rounds went.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub first: String,
+    pub last: String,
+    pub rounds: usize,
+    pub wins: usize,
+    pub ticks: usize,
+    pub opponents: usize,
+    pub deaths: BTreeMap<&'static str, usize>,
+}
+
+impl Summary {
+    pub fn add(&mut self, record: &RoundRecord) {
+        let date = record.date();
+        if self.rounds == 0 {
+            self.first = date.clone();
+        }
+        self.last = date;
+        self.rounds += 1;
+        self.ticks += record.ticks;
+        self.opponents += record.opponents;
+        if record.outcome == Outcome::Won {
+            self.wins += 1;
+        }
+        let death = match record.death {
+            Some(Death::OwnTrail) => "own trail",
+            Some(Death::OpponentTrail) => "opponent trail",
+            Some(Death::Wall) => "wall",
+            Some(Death::HeadOn) => "head-on",
+            None => return,
+        };
+        *self.deaths.entry(death).or_default() += 1;
+    }
+
+    pub fn win_rate(&self) -> f64 {
+        self.wins as f64 / self.rounds.max(1) as f64
+    }
+
+    pub fn average_ticks(&self) -> f64 {
+        self.ticks as f64 / self.rounds.max(1) as f64
+    }
+
+    pub fn average_opponents(&self) -> f64 {
+        self.opponents as f64 / self.rounds.max(1) as f64
+    }
+}
+
+/// `records` summarized per strategy and version, and per day as well if `daily`. Keys are
+/// `(strategy, version, day)`, with an empty day unless `daily`.
+pub fn summarize(records: &[RoundRecord], daily: bool) -> BTreeMap<(String, String, String), Summary> {
+    let mut summaries: BTreeMap<_, Summary> = BTreeMap::new();
+    for record in records {
+        let day = if daily { record.date() } else { String::new() };
+        summaries.entry((record.strategy.clone(), record.version.clone(), day)).or_default().add(record);
+    }
+    summaries
+}
+
+/// The date `days` days after 1970-01-01, see <https://howardhinnant.github.io/date_algorithms.html>.
+fn civil_date(days: u64) -> String {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use snek_protocol::Direction;
+
+    use super::*;
+
+    #[test]
+    fn tells_what_we_died_of() {
+        let mut round = GameRound::new(0, 5, 5);
+        round.move_player(0, 1, 1);
+        round.move_player(0, 2, 1);
+        round.move_player(1, 3, 3);
+
+        round.direction = Direction::Left;
+        assert_eq!(cause_of_death(&round), Death::OwnTrail);
+        round.direction = Direction::Right;
+        assert_eq!(cause_of_death(&round), Death::HeadOn);
+        round.board.take(round.offset(3, 1), 1);
+        assert_eq!(cause_of_death(&round), Death::OpponentTrail);
+    }
+
+    #[test]
+    fn summarizes_per_strategy_and_day() {
+        let round = GameRound::new(0, 10, 10);
+        let mut records = vec![
+            RoundRecord::new(&round, "snek", "minimax", Outcome::Won, None),
+            RoundRecord::new(&round, "snek", "minimax", Outcome::Lost, Some(Death::HeadOn)),
+            RoundRecord::new(&round, "snek", "mcts", Outcome::Lost, Some(Death::OwnTrail)),
+        ];
+        records[0].time_ms = 0;
+        records[1].time_ms = 86_400_000 * 19_000;
+
+        let overall = summarize(&records, false);
+        let minimax = &overall[&("minimax".to_string(), VERSION.to_string(), String::new())];
+        assert_eq!((minimax.rounds, minimax.wins, minimax.win_rate()), (2, 1, 0.5));
+        assert_eq!((minimax.first.as_str(), minimax.last.as_str()), ("1970-01-01", "2022-01-08"));
+        assert_eq!(minimax.deaths.get("head-on"), Some(&1));
+
+        let daily = summarize(&records, true);
+        assert_eq!(daily.len(), 3);
+    }
+}
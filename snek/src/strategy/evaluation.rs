@@ -0,0 +1,44 @@
+//! Scores for positions at the end of a search, see [`Minimax`](super::Minimax).
+
+use snek_protocol::Direction;
+
+use crate::analysis;
+use crate::GameRound;
+
+/// Scores a position from our point of view, higher is better. Only called for positions
+/// in which we are still alive.
+pub trait Evaluation: Send {
+    fn evaluate(&self, round: &GameRound) -> i32;
+}
+
+/// How many more cells we reach first than the strongest opponent.
+pub struct TerritoryEval;
+
+impl Evaluation for TerritoryEval {
+    fn evaluate(&self, round: &GameRound) -> i32 {
+        let heads: Vec<_> = round.head_offsets().collect();
+        let voronoi = analysis::voronoi(round, &heads);
+        let theirs = heads
+            .iter()
+            .filter(|&&(id, _)| id != round.player_id)
+            .map(|&(id, _)| voronoi.area_of(id))
+            .max()
+            .unwrap_or(0);
+        voronoi.area_of(round.player_id) as i32 - theirs as i32
+    }
+}
+
+/// How many cells we could still reach, ignoring everyone else.
+pub struct AreaEval;
+
+impl Evaluation for AreaEval {
+    fn evaluate(&self, round: &GameRound) -> i32 {
+        let head = round.offset(round.x, round.y);
+        Direction::ALL
+            .into_iter()
+            .filter_map(|dir| round.neighbour(head, dir))
+            .map(|next| analysis::reachable_area(round, next))
+            .max()
+            .unwrap_or(0) as i32
+    }
+}
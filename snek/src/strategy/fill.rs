@@ -0,0 +1,50 @@
+use snek_protocol::Direction;
+
+use crate::analysis;
+use crate::chamber;
+use crate::deadline::Deadline;
+use crate::endgame;
+use crate::strategy::Strategy;
+use crate::GameRound;
+
+/// Regions up to this many cells are searched for the longest path through them. Larger ones
+/// have too many paths to get anywhere near trying them all within a tick.
+const SEARCH_CELLS: usize = 64;
+
+/// Fills as many cells as possible, ignoring everyone else. What the other strategies switch
+/// to once [`chamber::is_isolated`] says nobody can get in our way anymore: the only thing
+/// left to decide then is how long we last.
+///
+/// Small regions are searched for the longest path through them. In large ones, we take the
+/// move with the most [`chamber::fillable_area`], capped by [`endgame::parity_bound`], and
+/// hug the walls on ties so we don't leave holes behind.
+pub struct Fill;
+
+impl Strategy for Fill {
+    fn name(&self) -> &'static str {
+        "fill"
+    }
+
+    fn next_move(&mut self, round: &GameRound, deadline: &Deadline) -> Direction {
+        let head = round.offset(round.x, round.y);
+        let ahead = round.direction;
+        let area = analysis::area_per_direction(round).into_iter().map(|(_, area)| area).max().unwrap_or(0);
+        if area <= SEARCH_CELLS {
+            if let Some(&dir) = endgame::longest_path(round, deadline).moves.first() {
+                return dir;
+            }
+        }
+
+        let free = round.board.free();
+        Direction::ALL
+            .into_iter()
+            .filter_map(|dir| Some((dir, round.free_neighbour(head, dir)?)))
+            .max_by_key(|&(dir, next)| {
+                let region = analysis::region(round, next);
+                let fillable = chamber::fillable_area(round, next).min(endgame::parity_bound(round, &region, next));
+                (fillable, std::cmp::Reverse(endgame::exits(round, &free, next)), dir == ahead)
+            })
+            .map(|(dir, _)| dir)
+            .unwrap_or(ahead)
+    }
+}
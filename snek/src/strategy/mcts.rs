@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use snek_protocol::Direction;
+
+use crate::chamber;
+use crate::deadline::{Deadline, TIME_SHARE};
+use crate::strategy::rollout::{self, RolloutPolicy};
+use crate::strategy::{self, Fill, Strategy};
+use crate::GameRound;
+
+/// Playouts stop after this many ticks from the root, counting the ticks spent in the tree.
+const HORIZON: usize = 40;
+
+/// Exploration constant of UCB1. Rewards are between 0 and 1.
+const EXPLORATION: f64 = 0.7;
+
+/// The moves of everyone at once, sorted by player id.
+type JointMove = Vec<(usize, Direction)>;
+
+/// How the moves of one player worked out for them in one node.
+#[derive(Debug)]
+struct PlayerStats {
+    id: usize,
+    moves: Vec<Direction>,
+    visits: Vec<u32>,
+    reward: Vec<f64>,
+}
+
+impl PlayerStats {
+    fn new(round: &GameRound, id: usize) -> PlayerStats {
+        let mut moves = rollout::free_moves(round, id);
+        if moves.is_empty() {
+            // Dead either way, one move is enough to represent that
+            moves.push(Direction::Up);
+        }
+
+        PlayerStats {
+            id,
+            visits: vec![0; moves.len()],
+            reward: vec![0.0; moves.len()],
+            moves,
+        }
+    }
+
+    /// UCB1, trying every move once before comparing them.
+    fn select(&self, total: u32, rng: &mut StdRng) -> usize {
+        let unvisited: Vec<_> = (0..self.moves.len()).filter(|&i| self.visits[i] == 0).collect();
+        if !unvisited.is_empty() {
+            return unvisited[rng.gen_range(0..unvisited.len())];
+        }
+
+        let log_total = (total as f64).ln();
+        let ucb = |i: usize| {
+            let visits = self.visits[i] as f64;
+            self.reward[i] / visits + EXPLORATION * (log_total / visits).sqrt()
+        };
+        (0..self.moves.len()).max_by(|&a, &b| ucb(a).total_cmp(&ucb(b))).unwrap_or(0)
+    }
+}
+
+/// A position in the tree. The position itself isn't stored, it's recreated by playing the
+/// joint moves from the root.
+#[derive(Debug, Default)]
+struct Node {
+    visits: u32,
+    /// Everyone alive in this position. Empty until the node is visited the second time.
+    players: Vec<PlayerStats>,
+    children: HashMap<JointMove, Node>,
+}
+
+/// The tree kept between ticks, together with the round it started from.
+struct Tree {
+    root: Node,
+    round: GameRound,
+}
+
+/// Monte Carlo tree search for rounds with many players, where trying every combination of
+/// moves is out of the question. Moves are simultaneous: every player picks their own move in
+/// each node with UCB1 over their own statistics (decoupled UCT), and positions new to the tree
+/// are played out with `P` until we die, one player is left, or [`HORIZON`] is reached.
+///
+/// The part of the tree matching what actually happened is kept for the next tick. Once
+/// nobody can reach us anymore, we [`Fill`] instead.
+pub struct Mcts<P> {
+    name: &'static str,
+    policy: P,
+    rng: StdRng,
+    tree: Option<Tree>,
+    /// Whether anyone besides us was alive at the root. Without opponents, being the last
+    /// one standing doesn't end a playout.
+    opponents: bool,
+}
+
+impl<P: RolloutPolicy> Mcts<P> {
+    pub fn new(name: &'static str, policy: P) -> Mcts<P> {
+        Mcts {
+            name,
+            policy,
+            rng: StdRng::from_entropy(),
+            tree: None,
+            opponents: false,
+        }
+    }
+
+    /// The tree of the last tick, moved on to the position the server reports now, or a new one.
+    fn take_tree(&mut self, round: &GameRound) -> Node {
+        let Some(mut tree) = self.tree.take() else {
+            return Node::default();
+        };
+
+        // Everyone in the old root has to still be alive and have moved on by one cell since,
+        // otherwise the positions below don't match the real one anymore
+        let mut joint = JointMove::new();
+        for stats in &tree.root.players {
+            let Some(player) = round.players.get(stats.id).filter(|p| p.alive) else {
+                return Node::default();
+            };
+            let (Some(dir), true) = (player.direction, player.previous_head == tree.round.head(stats.id)) else {
+                return Node::default();
+            };
+            joint.push((stats.id, dir));
+        }
+
+        tree.root.children.remove(&joint).unwrap_or_default()
+    }
+
+    fn is_over(&self, round: &GameRound, depth: usize) -> bool {
+        let alive = round.head_offsets().count();
+        let dead = round.head(round.player_id).is_none();
+        depth >= HORIZON || dead || (self.opponents && alive <= 1)
+    }
+
+    /// One round of selection, expansion, playout and backpropagation below `node`. Returns the
+    /// reward of every player, indexed by player id.
+    fn iterate(&mut self, node: &mut Node, round: &mut GameRound, depth: usize) -> Vec<f64> {
+        if self.is_over(round, depth) {
+            node.visits += 1;
+            return rewards(round, depth);
+        }
+
+        if node.visits == 0 {
+            node.visits += 1;
+            return self.playout(round, depth);
+        }
+
+        if node.players.is_empty() {
+            node.players = round.head_offsets().map(|(id, _)| PlayerStats::new(round, id)).collect();
+        }
+
+        let picks: Vec<usize> = node.players.iter().map(|stats| stats.select(node.visits, &mut self.rng)).collect();
+        let joint: JointMove = node.players.iter().zip(&picks).map(|(stats, &i)| (stats.id, stats.moves[i])).collect();
+
+        let died = round.step(&joint);
+        let child = node.children.entry(joint).or_default();
+        let mut rewards = self.iterate(child, round, depth + 1);
+        for id in died {
+            rewards[id] = survival(depth + 1);
+        }
+
+        node.visits += 1;
+        for (stats, &i) in node.players.iter_mut().zip(&picks) {
+            stats.visits[i] += 1;
+            stats.reward[i] += rewards[stats.id];
+        }
+        rewards
+    }
+
+    fn playout(&mut self, round: &mut GameRound, mut depth: usize) -> Vec<f64> {
+        let mut died_at = vec![None; round.players.len()];
+        while !self.is_over(round, depth) {
+            let joint: JointMove = round
+                .head_offsets()
+                .map(|(id, _)| (id, self.policy.choose(round, id, &mut self.rng)))
+                .collect();
+
+            depth += 1;
+            for id in round.step(&joint) {
+                died_at[id] = Some(depth);
+            }
+        }
+
+        let mut rewards = rewards(round, depth);
+        for (id, died) in died_at.into_iter().enumerate() {
+            if let Some(depth) = died {
+                rewards[id] = survival(depth);
+            }
+        }
+        rewards
+    }
+}
+
+impl<P: RolloutPolicy> Strategy for Mcts<P> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn new_round(&mut self, _round: &GameRound) {
+        self.tree = None;
+    }
+
+    fn next_move(&mut self, round: &GameRound, deadline: &Deadline) -> Direction {
+        if chamber::is_isolated(round) {
+            self.tree = None;
+            return Fill.next_move(round, deadline);
+        }
+
+        self.opponents = round.head_offsets().any(|(id, _)| id != round.player_id);
+        let mut root = self.take_tree(round);
+        // Every iteration plays on the same copy, undone afterwards, instead of copying the
+        // whole board each time
+        let mut copy = round.clone();
+        let checkpoint = copy.checkpoint();
+        while deadline.used() < TIME_SHARE {
+            self.iterate(&mut root, &mut copy, 0);
+            copy.undo(&checkpoint);
+        }
+
+        let best = root
+            .players
+            .iter()
+            .find(|stats| stats.id == round.player_id)
+            .and_then(|stats| (0..stats.moves.len()).max_by_key(|&i| stats.visits[i]).map(|i| stats.moves[i]))
+            .unwrap_or_else(|| strategy::safe_move(round));
+
+        self.tree = Some(Tree { root, round: round.clone() });
+        best
+    }
+
+    /// The average reward of each of our moves at the root.
+    fn scores(&self) -> Vec<(Direction, f64)> {
+        let Some(tree) = &self.tree else {
+            return vec![];
+        };
+        let Some(stats) = tree.root.players.iter().find(|stats| stats.id == tree.round.player_id) else {
+            return vec![];
+        };
+        (0..stats.moves.len())
+            .map(|i| (stats.moves[i], stats.reward[i] / stats.visits[i].max(1) as f64))
+            .collect()
+    }
+}
+
+/// The reward for dying `depth` ticks from the root: the longer we last, the better.
+fn survival(depth: usize) -> f64 {
+    0.5 * depth as f64 / HORIZON as f64
+}
+
+/// Rewards for everyone once a playout ends `depth` ticks from the root. The last one standing
+/// gets the full reward, survivors of a playout that hit the horizon a bit less. The dead get
+/// what they earned by surviving as long as they did, callers fix this up for those who
+/// didn't make it to `depth`.
+fn rewards(round: &GameRound, depth: usize) -> Vec<f64> {
+    let alive = round.head_offsets().count();
+    round
+        .players
+        .iter()
+        .map(|player| match player.alive {
+            true if alive == 1 => 1.0,
+            true => 0.75,
+            false => survival(depth),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::rollout::RandomRollout;
+
+    fn seeded() -> Mcts<RandomRollout> {
+        Mcts { rng: StdRng::seed_from_u64(3), ..Mcts::new("mcts", RandomRollout) }
+    }
+
+    /// Three players spread over an empty 10x10 map, we are player 0.
+    fn three_players() -> GameRound {
+        let mut round = GameRound::new(0, 10, 10);
+        round.move_player(0, 2, 2);
+        round.move_player(1, 7, 3);
+        round.move_player(2, 4, 8);
+        round
+    }
+
+    #[test]
+    fn rewards_are_per_player_and_in_range() {
+        let round = three_players();
+        let mut mcts = seeded();
+        mcts.opponents = true;
+        let mut root = Node::default();
+        for _ in 0..500 {
+            let rewards = mcts.iterate(&mut root, &mut round.clone(), 0);
+            assert_eq!(rewards.len(), 3);
+            assert!(rewards.iter().all(|reward| (0.0..=1.0).contains(reward)), "{rewards:?}");
+        }
+
+        let mut ended = round.clone();
+        ended.remove_players(&[2]);
+        assert_eq!(rewards(&ended, 10), vec![0.75, 0.75, survival(10)]);
+        ended.remove_players(&[1]);
+        assert_eq!(rewards(&ended, 10), vec![1.0, survival(10), survival(10)]);
+    }
+
+    #[test]
+    fn keeps_the_subtree_of_what_happened() {
+        let round = three_players();
+        let mut mcts = seeded();
+        mcts.opponents = true;
+        let mut root = Node::default();
+        for _ in 0..500 {
+            mcts.iterate(&mut root, &mut round.clone(), 0);
+        }
+
+        // Everyone moves the way the most visited child of the root says
+        let (joint, child) = root.children.iter().max_by_key(|(_, child)| child.visits).unwrap();
+        let (joint, visits) = (joint.clone(), child.visits);
+        let child_visits: Vec<u32> = child.players.iter().flat_map(|stats| stats.visits.clone()).collect();
+        let mut moved = round.clone();
+        for &(id, dir) in &joint {
+            let (x, y) = moved.head(id).unwrap();
+            let (x, y) = moved.position(moved.next_offset(x, y, dir).unwrap());
+            moved.move_player(id, x, y);
+        }
+
+        mcts.tree = Some(Tree { root, round: round.clone() });
+        let kept = mcts.take_tree(&moved);
+        assert!(visits > 1);
+        assert_eq!(kept.visits, visits);
+        assert_eq!(kept.players.iter().flat_map(|stats| stats.visits.clone()).collect::<Vec<_>>(), child_visits);
+
+        // Nothing matches once someone turns up somewhere else
+        mcts.tree = Some(Tree { root: kept, round: moved.clone() });
+        let mut jumped = moved.clone();
+        jumped.move_player(1, 0, 9);
+        assert_eq!(mcts.take_tree(&jumped).visits, 0);
+    }
+}
@@ -0,0 +1,474 @@
+use snek_protocol::{Direction, Edges};
+
+use crate::chamber;
+use crate::deadline::{Deadline, TIME_SHARE};
+use crate::opponent;
+use crate::strategy::{self, Evaluation, Fill, Strategy};
+use crate::GameRound;
+
+const WIN: i32 = 1_000_000;
+const LOSS: i32 = -WIN;
+/// Dying together with the last opponent. Better than losing alone, but nothing to aim for.
+const DRAW: i32 = LOSS / 2;
+
+/// Never search deeper than this many of our moves.
+const MAX_DEPTH: u32 = 64;
+
+/// Each iteration takes roughly this many times as long as the one before. Used to skip
+/// iterations that wouldn't finish in time anyway.
+const GROWTH: u32 = 3;
+
+/// The search ran out of time and its result can't be used.
+struct Aborted;
+
+/// What the search assumes about the moves of opponents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Replies {
+    /// They make whatever move is worst for us.
+    WorstCase,
+    /// They move the way [`opponent::predict`] expects, from what we've seen of them so far.
+    Expected,
+}
+
+/// Paranoid alpha-beta search with iterative deepening: we pick a move, then all opponents
+/// pick theirs together, assuming they are out to get us. Both are applied at once on a copy
+/// of the round, the same way the server does it, and positions at the end of the search are
+/// scored by `E`. With [`Replies::Expected`], opponents make their likely moves instead,
+/// which pays off against bots that follow a simple rule, but can't prune as much.
+///
+/// Opponents too far away to reach us within the search depth stand still, which keeps the
+/// number of joint moves down in crowded rounds. Once nobody can reach us anymore, we [`Fill`].
+pub struct Minimax<E> {
+    eval: E,
+    replies: Replies,
+    /// Scores of our moves in the deepest search that finished.
+    scores: Vec<(Direction, i32)>,
+    /// Our moves left until a win no opponent can prevent, if the last search found one.
+    certain_win: Option<i32>,
+    /// Whether we told everyone about a certain win this round already.
+    announced_win: bool,
+}
+
+impl<E: Evaluation> Minimax<E> {
+    pub fn new(eval: E) -> Minimax<E> {
+        Minimax { eval, replies: Replies::WorstCase, scores: vec![], certain_win: None, announced_win: false }
+    }
+
+    /// A search expecting opponents to play like they did so far, see [`Replies::Expected`].
+    pub fn expecting(eval: E) -> Minimax<E> {
+        Minimax { eval, replies: Replies::Expected, scores: vec![], certain_win: None, announced_win: false }
+    }
+
+    /// The score of each of `moves` when looking `depth` of our moves ahead. Only the best
+    /// score is exact, the others are just known to be no better than it.
+    fn search_root(
+        &self,
+        round: &GameRound,
+        moves: &[Direction],
+        opponents: &[usize],
+        depth: u32,
+        deadline: &Deadline,
+    ) -> Result<Vec<(Direction, i32)>, Aborted> {
+        let mut scores = Vec::with_capacity(moves.len());
+        let mut alpha = i32::MIN;
+        for &dir in moves {
+            let score = self.min_node(round, dir, opponents, depth, 1, alpha, i32::MAX, deadline)?;
+            alpha = alpha.max(score);
+            scores.push((dir, score));
+        }
+        Ok(scores)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn max_node(
+        &self,
+        round: &GameRound,
+        opponents: &[usize],
+        depth: u32,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+        deadline: &Deadline,
+    ) -> Result<i32, Aborted> {
+        if deadline.used() >= TIME_SHARE {
+            return Err(Aborted);
+        }
+        if depth == 0 {
+            return Ok(self.eval.evaluate(round));
+        }
+
+        let mut best = LOSS + ply;
+        for dir in candidate_moves(round, round.player_id) {
+            best = best.max(self.min_node(round, dir, opponents, depth, ply, alpha, beta, deadline)?);
+            alpha = alpha.max(best);
+            if alpha >= beta {
+                break;
+            }
+        }
+        Ok(best)
+    }
+
+    /// Tries every joint reply of the opponents to us moving in `ours`. Takes the worst one
+    /// for us, or the average weighted by how likely each reply is if we expect opponents to
+    /// play the way we've seen them play.
+    #[allow(clippy::too_many_arguments)]
+    fn min_node(
+        &self,
+        round: &GameRound,
+        ours: Direction,
+        opponents: &[usize],
+        depth: u32,
+        ply: i32,
+        alpha: i32,
+        mut beta: i32,
+        deadline: &Deadline,
+    ) -> Result<i32, Aborted> {
+        if self.replies == Replies::Expected {
+            let mut expected = 0.0;
+            for (replies, chance) in weighted_joint_moves(round, opponents) {
+                // Every reply counts, so there is nothing to cut
+                let score = self.reply(round, ours, replies, opponents, depth, ply, i32::MIN, i32::MAX, deadline)?;
+                expected += chance * score as f64;
+            }
+            return Ok(expected.round() as i32);
+        }
+
+        let mut best = i32::MAX;
+        for replies in joint_moves(round, opponents) {
+            let score = self.reply(round, ours, replies, opponents, depth, ply, alpha, beta, deadline)?;
+            best = best.min(score);
+            beta = beta.min(best);
+            if alpha >= beta {
+                break;
+            }
+        }
+        Ok(best)
+    }
+
+    /// The score after we move in `ours` and the opponents make `replies`.
+    #[allow(clippy::too_many_arguments)]
+    fn reply(
+        &self,
+        round: &GameRound,
+        ours: Direction,
+        replies: Vec<(usize, Direction)>,
+        opponents: &[usize],
+        depth: u32,
+        ply: i32,
+        alpha: i32,
+        beta: i32,
+        deadline: &Deadline,
+    ) -> Result<i32, Aborted> {
+        let mut moves = vec![(round.player_id, ours)];
+        moves.extend(replies);
+
+        let mut next = round.clone();
+        let died = next.step(&moves);
+        let alive: Vec<usize> = opponents.iter().copied().filter(|id| !died.contains(id)).collect();
+        let opponents_left = next.head_offsets().any(|(id, _)| id != round.player_id);
+
+        if died.contains(&round.player_id) {
+            Ok(if opponents_left { LOSS + ply } else { DRAW + ply })
+        } else if !opponents.is_empty() && !opponents_left {
+            Ok(WIN - ply)
+        } else {
+            self.max_node(&next, &alive, depth - 1, ply + 1, alpha, beta, deadline)
+        }
+    }
+}
+
+impl<E: Evaluation> Strategy for Minimax<E> {
+    fn name(&self) -> &'static str {
+        match self.replies {
+            Replies::WorstCase => "minimax",
+            Replies::Expected => "expectimax",
+        }
+    }
+
+    fn new_round(&mut self, _round: &GameRound) {
+        self.announced_win = false;
+    }
+
+    fn next_move(&mut self, round: &GameRound, deadline: &Deadline) -> Direction {
+        self.scores.clear();
+        self.certain_win = None;
+        if chamber::is_isolated(round) {
+            return Fill.next_move(round, deadline);
+        }
+
+        let mut moves = candidate_moves(round, round.player_id);
+        let mut best = strategy::safe_move(round);
+        if moves.len() < 2 {
+            return best;
+        }
+        // Try the most promising move first, so alpha-beta can cut more
+        moves.sort_by_key(|&dir| dir != best);
+
+        let time_left = deadline.budget().mul_f64(TIME_SHARE);
+        for depth in 1..=MAX_DEPTH {
+            let started = deadline.elapsed();
+            let opponents = opponents_within(round, 2 * depth as usize);
+            let Ok(scores) = self.search_root(round, &moves, &opponents, depth, deadline) else {
+                break;
+            };
+
+            // The first move with the best score, which is the best of the last iteration on ties
+            let (dir, score) = scores.iter().fold(scores[0], |best, &next| if next.1 > best.1 { next } else { best });
+            self.scores = scores;
+            best = dir;
+            moves.sort_by_key(|&dir| dir != best);
+            // Won, lost or drawn no matter what, looking further doesn't change that
+            if score.abs() >= WIN / 4 {
+                // Expected replies are only likely, a win against them is anything but certain
+                if score > 0 && self.replies == Replies::WorstCase {
+                    self.certain_win = Some(WIN - score);
+                }
+                break;
+            }
+
+            let iteration = deadline.elapsed() - started;
+            if deadline.elapsed() + iteration * GROWTH > time_left {
+                break;
+            }
+        }
+
+        best
+    }
+
+    fn scores(&self) -> Vec<(Direction, f64)> {
+        self.scores.iter().map(|&(dir, score)| (dir, score as f64)).collect()
+    }
+
+    /// Announces a certain win once per round, while the round still goes on. Once the
+    /// server tells us we won, the round is over and nobody hears what we say anymore.
+    fn chat(&mut self, _round: &GameRound) -> Option<String> {
+        let moves = self.certain_win.filter(|_| !self.announced_win)?;
+        self.announced_win = true;
+        Some(format!("gg, I win in at most {moves} moves"))
+    }
+}
+
+/// The moves of player `id` that don't run into a trail right away. If there are none,
+/// a single move to represent dying.
+fn candidate_moves(round: &GameRound, id: usize) -> Vec<Direction> {
+    let Some((x, y)) = round.head(id) else {
+        return vec![];
+    };
+
+    let moves: Vec<_> = Direction::ALL
+        .into_iter()
+        .filter(|&dir| !round.is_move_blocked(x, y, dir))
+        .collect();
+    if moves.is_empty() {
+        vec![Direction::Up]
+    } else {
+        moves
+    }
+}
+
+/// Every combination of moves `opponents` can make together.
+fn joint_moves(round: &GameRound, opponents: &[usize]) -> Vec<Vec<(usize, Direction)>> {
+    let mut joint = vec![vec![]];
+    for &id in opponents {
+        let moves = candidate_moves(round, id);
+        joint = joint
+            .into_iter()
+            .flat_map(|partial: Vec<(usize, Direction)>| {
+                moves.iter().map(move |&dir| {
+                    let mut combined = partial.clone();
+                    combined.push((id, dir));
+                    combined
+                })
+            })
+            .collect();
+    }
+    joint
+}
+
+/// Like [`joint_moves`], with the chance of each combination according to the opponent models.
+fn weighted_joint_moves(round: &GameRound, opponents: &[usize]) -> Vec<(Vec<(usize, Direction)>, f64)> {
+    let mut joint = vec![(vec![], 1.0)];
+    for &id in opponents {
+        let mut moves = opponent::predict(round, id);
+        if moves.is_empty() {
+            moves.push((Direction::Up, 1.0));
+        }
+        joint = joint
+            .into_iter()
+            .flat_map(|(partial, chance): (Vec<(usize, Direction)>, f64)| {
+                moves.iter().map(move |&(dir, p)| {
+                    let mut combined = partial.clone();
+                    combined.push((id, dir));
+                    (combined, chance * p)
+                })
+            })
+            .collect();
+    }
+    joint
+}
+
+/// Opponents whose head is at most `distance` steps from ours, going around the edges if the
+/// map wraps.
+fn opponents_within(round: &GameRound, distance: usize) -> Vec<usize> {
+    let wraps = round.rules.edges == Edges::Wrap;
+    let wrapped = |a: usize, b: usize, size: usize| {
+        let d = a.abs_diff(b);
+        if wraps { d.min(size - d) } else { d }
+    };
+
+    round
+        .head_offsets()
+        .filter(|&(id, _)| id != round.player_id)
+        .filter_map(|(id, offset)| {
+            let (x, y) = round.position(offset);
+            let steps = wrapped(x, round.x, round.width) + wrapped(y, round.y, round.height);
+            (steps <= distance).then_some(id)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use snek_protocol::Rules;
+
+    use super::*;
+    use crate::strategy::TerritoryEval;
+
+    /// A round on a walled map drawn as rows of `.` for free cells, `#` for trails, and `0` or
+    /// `1` for the heads of the players. We are player 0.
+    fn walled(rows: &[&str]) -> GameRound {
+        let rules = Rules { edges: Edges::Walls, ..Rules::default() };
+        let mut round = GameRound::with_rules(0, rows[0].len(), rows.len(), rules);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                match cell {
+                    '#' => round.board.take(round.offset(x, y), 1),
+                    '0' | '1' => round.move_player(cell.to_digit(10).unwrap() as usize, x, y),
+                    _ => {}
+                }
+            }
+        }
+        round
+    }
+
+    fn best(scores: &[(Direction, i32)]) -> Direction {
+        scores.iter().fold(scores[0], |best, &next| if next.1 > best.1 { next } else { best }).0
+    }
+
+    /// Likes being far to the left, and nothing else. Takes `slow` to answer once it's been
+    /// asked `fast` times.
+    struct Lure {
+        calls: AtomicUsize,
+        fast: usize,
+        slow: Duration,
+    }
+
+    impl Lure {
+        fn new() -> Lure {
+            Lure { calls: AtomicUsize::new(0), fast: usize::MAX, slow: Duration::ZERO }
+        }
+    }
+
+    impl Evaluation for Lure {
+        fn evaluate(&self, round: &GameRound) -> i32 {
+            if self.calls.fetch_add(1, Ordering::Relaxed) >= self.fast {
+                thread::sleep(self.slow);
+            }
+            -(round.x as i32) * 100_000
+        }
+    }
+
+    /// Going left leads into a dead end two cells deep, which the lure can't resist.
+    const TRAP: [&str; 5] = [
+        ".........",
+        "###......",
+        "#..0.....",
+        "###......",
+        "........1",
+    ];
+
+    #[test]
+    fn takes_the_only_move_that_survives() {
+        let round = walled(&[
+            "##.....",
+            ".#.....",
+            "0......",
+            "##.....",
+            "......1",
+        ]);
+        let mut minimax = Minimax::new(TerritoryEval);
+        assert_eq!(minimax.next_move(&round, &Deadline::new(Duration::from_secs(1))), Direction::Right);
+    }
+
+    #[test]
+    fn sees_traps_once_deep_enough() {
+        let round = walled(&TRAP);
+        let minimax = Minimax::new(Lure::new());
+        let deadline = Deadline::new(Duration::from_secs(10));
+        let search = |depth| best(&minimax.search_root(&round, &candidate_moves(&round, 0), &[], depth, &deadline).ok().unwrap());
+
+        assert_eq!(search(1), Direction::Left);
+        assert_eq!(search(2), Direction::Left);
+        assert_ne!(search(3), Direction::Left);
+    }
+
+    #[test]
+    fn out_of_time_keeps_the_last_finished_depth() {
+        // The first depth takes four evaluations, the next one runs out of time
+        let round = walled(&TRAP);
+        let mut minimax = Minimax::new(Lure { fast: 4, slow: Duration::from_millis(50), ..Lure::new() });
+
+        assert_eq!(minimax.next_move(&round, &Deadline::new(Duration::from_millis(20))), Direction::Left);
+        // Scored at the first depth, a move later going left would look even better
+        assert!(minimax.scores().contains(&(Direction::Left, -200_000.0)));
+    }
+
+    #[test]
+    fn expected_replies_take_risks_worst_case_ones_dont() {
+        // Player 1 came up from below. They are unlikely to turn right into the cell left of
+        // us, but they could, and we'd both die.
+        let mut round = walled(&[
+            "...#...",
+            ".1.0#..",
+            ".#.....",
+        ]);
+        round.players[1].direction = Some(Direction::Up);
+
+        let moves = candidate_moves(&round, 0);
+        assert_eq!(moves, vec![Direction::Down, Direction::Left]);
+        let deadline = Deadline::new(Duration::from_secs(10));
+        let worst_case = Minimax::new(Lure::new()).search_root(&round, &moves, &[1], 1, &deadline).ok().unwrap();
+        let expected = Minimax::expecting(Lure::new()).search_root(&round, &moves, &[1], 1, &deadline).ok().unwrap();
+
+        assert_eq!(worst_case[1], (Direction::Left, DRAW + 1));
+        assert_eq!(best(&worst_case), Direction::Down);
+        assert!(expected[1].1 > worst_case[1].1);
+        assert_eq!(best(&expected), Direction::Left);
+    }
+
+    #[test]
+    fn announces_a_certain_win_once_per_round() {
+        // Shutting the top row, player 1 has three cells left, we have plenty
+        let round = walled(&[
+            "1.....",
+            "####0.",
+            "......",
+        ]);
+        let mut minimax = Minimax::new(TerritoryEval);
+        let deadline = Deadline::new(Duration::from_secs(1));
+        assert_eq!(minimax.chat(&round), None);
+
+        assert_eq!(minimax.next_move(&round, &deadline), Direction::Up);
+        assert_eq!(minimax.chat(&round), Some("gg, I win in at most 4 moves".to_string()));
+        minimax.next_move(&round, &deadline);
+        assert_eq!(minimax.chat(&round), None);
+
+        minimax.new_round(&round);
+        minimax.next_move(&round, &deadline);
+        assert!(minimax.chat(&round).is_some());
+    }
+}
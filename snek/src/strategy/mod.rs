@@ -0,0 +1,91 @@
+//! The bot's decision logic. Every strategy gets a read-only view of the current
+//! [`GameRound`] on each tick and answers with the direction to move in.
+
+mod evaluation;
+mod fill;
+mod mcts;
+mod minimax;
+mod rollout;
+mod simple;
+mod straight;
+mod territory;
+
+use snek_protocol::Direction;
+
+use crate::collision::{self, Safety};
+use crate::deadline::Deadline;
+use crate::GameRound;
+
+pub use evaluation::{AreaEval, Evaluation, TerritoryEval};
+pub use fill::Fill;
+pub use mcts::Mcts;
+pub use minimax::{Minimax, Replies};
+pub use rollout::{FloodFillRollout, ModelRollout, RandomRollout, RolloutPolicy};
+pub use simple::Simple;
+pub use straight::Straight;
+pub use territory::Territory;
+
+/// Strategies run on their own thread while the client keeps an eye on the clock,
+/// hence the `Send`.
+pub trait Strategy: Send {
+    /// The name the strategy is selected by on the command line.
+    fn name(&self) -> &'static str;
+
+    /// Called once the first positions of a new round are known, before the first
+    /// call to [`Strategy::next_move`]. Strategies keeping state across ticks reset it here.
+    fn new_round(&mut self, _round: &GameRound) {}
+
+    /// Decides on the next move. Strategies that can always think a bit longer should stop
+    /// once `deadline` expires. When a strategy takes long, the client sends [`safe_move`]
+    /// in the meantime and only replaces it if the answer still comes before the deadline.
+    fn next_move(&mut self, round: &GameRound, deadline: &Deadline) -> Direction;
+
+    /// How the strategy rated the moves it considered on its last call to
+    /// [`Strategy::next_move`], higher is better. Only the order within one tick means
+    /// anything, scores aren't comparable between strategies. Used for telemetry.
+    fn scores(&self) -> Vec<(Direction, f64)> {
+        vec![]
+    }
+
+    /// Something to say to the other players, asked after every move. Goes through the same
+    /// rate limit as everything else we say, so it may not be sent.
+    fn chat(&mut self, _round: &GameRound) -> Option<String> {
+        None
+    }
+}
+
+/// A move that takes next to no time to find and doesn't kill us right away if that can be
+/// helped: the free neighbour nobody else can reach next tick with the most free neighbours of
+/// its own, preferring to go straight.
+pub fn safe_move(round: &GameRound) -> Direction {
+    let head = round.offset(round.x, round.y);
+    let ahead = round.direction;
+    collision::classify_moves(round)
+        .into_iter()
+        .filter(|&(_, safety)| safety != Safety::Lethal)
+        .max_by_key(|&(dir, safety)| {
+            let next = round.neighbour(head, dir).expect("Only moves onto the map aren't lethal");
+            let exits = Direction::ALL.iter().filter(|&&d| round.free_neighbour(next, d).is_some()).count();
+            (safety == Safety::Safe, exits, dir == ahead)
+        })
+        .map(|(dir, _)| dir)
+        .unwrap_or(ahead)
+}
+
+/// Names of all strategies known to [`by_name`].
+pub const NAMES: &[&str] = &["simple", "straight", "territory", "minimax", "expectimax", "mcts", "mcts-model", "mcts-random", "fill"];
+
+pub fn by_name(name: &str) -> Option<Box<dyn Strategy>> {
+    match name {
+        "simple" => Some(Box::new(Simple)),
+        "straight" => Some(Box::new(Straight)),
+        "territory" => Some(Box::new(Territory::default())),
+        "minimax" => Some(Box::new(Minimax::new(TerritoryEval))),
+        "expectimax" => Some(Box::new(Minimax::expecting(TerritoryEval))),
+        "mcts" => Some(Box::new(Mcts::new("mcts", FloodFillRollout::default()))),
+        "mcts-model" => Some(Box::new(Mcts::new("mcts-model", ModelRollout { ours: FloodFillRollout::default() }))),
+        "mcts-random" => Some(Box::new(Mcts::new("mcts-random", RandomRollout))),
+        "fill" => Some(Box::new(Fill)),
+        _ => None,
+    }
+}
@@ -0,0 +1,142 @@
+//! How players move in the random playouts of [`Mcts`](super::Mcts).
+
+use rand::rngs::StdRng;
+use rand::Rng;
+use snek_protocol::Direction;
+
+use crate::analysis;
+use crate::opponent;
+use crate::GameRound;
+
+pub trait RolloutPolicy: Send {
+    /// The move player `id` makes next. Only called for players that are still alive.
+    fn choose(&self, round: &GameRound, id: usize, rng: &mut StdRng) -> Direction;
+}
+
+/// Any move that doesn't run into a trail right away, picked uniformly.
+pub struct RandomRollout;
+
+impl RolloutPolicy for RandomRollout {
+    fn choose(&self, round: &GameRound, id: usize, rng: &mut StdRng) -> Direction {
+        let moves = free_moves(round, id);
+        if moves.is_empty() {
+            return Direction::Up;
+        }
+        moves[rng.gen_range(0..moves.len())]
+    }
+}
+
+/// Picks moves with a chance proportional to the area behind them, counted up to `limit`
+/// cells, so players in the playout rarely wander into dead ends.
+pub struct FloodFillRollout {
+    pub limit: usize,
+}
+
+impl Default for FloodFillRollout {
+    fn default() -> FloodFillRollout {
+        FloodFillRollout { limit: 16 }
+    }
+}
+
+impl RolloutPolicy for FloodFillRollout {
+    fn choose(&self, round: &GameRound, id: usize, rng: &mut StdRng) -> Direction {
+        let Some((x, y)) = round.head(id) else {
+            return Direction::Up;
+        };
+
+        let head = round.offset(x, y);
+        let weighted: Vec<_> = free_moves(round, id)
+            .into_iter()
+            .filter_map(|dir| Some((dir, round.neighbour(head, dir)?)))
+            .map(|(dir, next)| (dir, analysis::reachable_area_up_to(round, next, self.limit)))
+            .collect();
+
+        let total: usize = weighted.iter().map(|&(_, area)| area).sum();
+        if total == 0 {
+            return Direction::Up;
+        }
+
+        let mut pick = rng.gen_range(0..total);
+        for (dir, area) in weighted {
+            if pick < area {
+                return dir;
+            }
+            pick -= area;
+        }
+        unreachable!("The pick is smaller than the total area")
+    }
+}
+
+/// Opponents move the way [`opponent::predict`] expects them to, we move according to `P`.
+pub struct ModelRollout<P> {
+    pub ours: P,
+}
+
+impl<P: RolloutPolicy> RolloutPolicy for ModelRollout<P> {
+    fn choose(&self, round: &GameRound, id: usize, rng: &mut StdRng) -> Direction {
+        if id == round.player_id {
+            return self.ours.choose(round, id, rng);
+        }
+
+        let mut pick = rng.gen::<f64>();
+        let prediction = opponent::predict(round, id);
+        for &(dir, chance) in &prediction {
+            if pick < chance {
+                return dir;
+            }
+            pick -= chance;
+        }
+        // Rounding errors, or no free move at all
+        prediction.last().map_or(Direction::Up, |&(dir, _)| dir)
+    }
+}
+
+/// The moves of player `id` that don't run into a trail right away.
+pub fn free_moves(round: &GameRound, id: usize) -> Vec<Direction> {
+    let Some((x, y)) = round.head(id) else {
+        return vec![];
+    };
+
+    Direction::ALL
+        .into_iter()
+        .filter(|&dir| !round.is_move_blocked(x, y, dir))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use snek_protocol::{Edges, Rules};
+
+    use super::*;
+
+    #[test]
+    fn rollouts_only_pick_free_moves() {
+        // Player 0 is in the top left corner with a trail to their right, player 1 at the
+        // right edge with a trail to their left
+        let rules = Rules { edges: Edges::Walls, ..Rules::default() };
+        let mut round = GameRound::with_rules(0, 5, 5, rules);
+        round.board.take(round.offset(1, 0), 1);
+        round.board.take(round.offset(3, 2), 1);
+        round.move_player(0, 0, 0);
+        round.move_player(1, 4, 2);
+        assert_eq!(free_moves(&round, 0), vec![Direction::Down]);
+        assert_eq!(free_moves(&round, 1), vec![Direction::Up, Direction::Down]);
+
+        let policies: [(&str, Box<dyn RolloutPolicy>); 3] = [
+            ("random", Box::new(RandomRollout)),
+            ("flood fill", Box::new(FloodFillRollout::default())),
+            ("model", Box::new(ModelRollout { ours: RandomRollout })),
+        ];
+        let mut rng = StdRng::seed_from_u64(7);
+        for (name, policy) in &policies {
+            for id in [0, 1] {
+                let free = free_moves(&round, id);
+                for _ in 0..100 {
+                    let dir = policy.choose(&round, id, &mut rng);
+                    assert!(free.contains(&dir), "{name} moved player {id} {dir}");
+                }
+            }
+        }
+    }
+}
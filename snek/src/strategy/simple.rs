@@ -0,0 +1,24 @@
+use snek_protocol::Direction;
+
+use crate::deadline::Deadline;
+use crate::strategy::Strategy;
+use crate::GameRound;
+
+/// Goes up until that is blocked, then tries right, then left.
+pub struct Simple;
+
+impl Strategy for Simple {
+    fn name(&self) -> &'static str {
+        "simple"
+    }
+
+    fn next_move(&mut self, round: &GameRound, _deadline: &Deadline) -> Direction {
+        if !round.is_move_blocked(round.x, round.y, Direction::Up) {
+            Direction::Up
+        } else if !round.is_move_blocked(round.x, round.y, Direction::Right) {
+            Direction::Right
+        } else {
+            Direction::Left
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use snek_protocol::Direction;
+
+use crate::deadline::Deadline;
+use crate::strategy::Strategy;
+use crate::GameRound;
+
+/// Keeps going in the current direction and only turns, right before left,
+/// when the next cell is taken.
+pub struct Straight;
+
+impl Strategy for Straight {
+    fn name(&self) -> &'static str {
+        "straight"
+    }
+
+    fn next_move(&mut self, round: &GameRound, _deadline: &Deadline) -> Direction {
+        let ahead = round.direction;
+        [ahead, ahead.turn_right(), ahead.turn_left()]
+            .into_iter()
+            .find(|&dir| !round.is_move_blocked(round.x, round.y, dir))
+            .unwrap_or(ahead)
+    }
+}
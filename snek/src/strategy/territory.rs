@@ -0,0 +1,53 @@
+use snek_protocol::Direction;
+
+use crate::analysis;
+use crate::chamber;
+use crate::collision;
+use crate::deadline::Deadline;
+use crate::strategy::{Fill, Strategy};
+use crate::GameRound;
+
+/// Picks the move that leaves us the most cells we reach before anyone else, preferring the
+/// larger reachable area and then going straight on ties. Territory behind a cell an opponent
+/// might move into as well only counts by the chance that we survive getting there.
+///
+/// Once nobody can reach us anymore, there's no territory left to fight over and we [`Fill`].
+#[derive(Default)]
+pub struct Territory {
+    scores: Vec<(Direction, f64)>,
+}
+
+impl Strategy for Territory {
+    fn name(&self) -> &'static str {
+        "territory"
+    }
+
+    fn next_move(&mut self, round: &GameRound, deadline: &Deadline) -> Direction {
+        self.scores.clear();
+        if chamber::is_isolated(round) {
+            return Fill.next_move(round, deadline);
+        }
+
+        let ahead = round.direction;
+        let reach = collision::opponent_reach(round);
+        let candidates: Vec<_> = analysis::area_per_direction(round)
+            .into_iter()
+            .filter_map(|(dir, area)| {
+                let territory = analysis::territory_after_move(round, dir)?;
+                let survival = 1.0 - collision::classify(round, &reach, dir).cost(round.alive_players, round.rules.head_on);
+                Some((territory as f64 * survival, area, dir == ahead, dir))
+            })
+            .collect();
+
+        self.scores = candidates.iter().map(|&(score, .., dir)| (dir, score)).collect();
+        candidates
+            .into_iter()
+            .max_by(|a, b| a.0.total_cmp(&b.0).then((a.1, a.2).cmp(&(b.1, b.2))))
+            .map(|(.., dir)| dir)
+            .unwrap_or(ahead)
+    }
+
+    fn scores(&self) -> Vec<(Direction, f64)> {
+        self.scores.clone()
+    }
+}
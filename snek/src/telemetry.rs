@@ -0,0 +1,109 @@
+//! A record of every decision the bot makes, for looking into how a strategy played after
+//! the fact. Records are written as JSON lines, one object per tick, which loads straight into
+//! most data tools, e.g. `pandas.read_json(path, lines=True)`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use snek_protocol::Direction;
+
+/// What happened on one tick.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TickRecord {
+    /// Milliseconds since the unix epoch, for telling rounds and sessions apart.
+    pub time_ms: u128,
+    /// Who we play as, for telling bots sharing a file apart.
+    pub username: String,
+    pub player_id: usize,
+    pub tick: usize,
+    /// Players alive at the start of the tick, us included.
+    pub alive: usize,
+    pub strategy: &'static str,
+    /// The move the strategy decided on. `None` if it overran its budget.
+    pub direction: Option<&'static str>,
+    /// The move sent while the strategy was still thinking, if it took that long.
+    pub fallback: Option<&'static str>,
+    /// The strategy's rating of each move it considered, see [`Strategy::scores`].
+    ///
+    /// [`Strategy::scores`]: crate::strategy::Strategy::scores
+    pub scores: BTreeMap<&'static str, f64>,
+    pub elapsed_ms: f64,
+    /// Share of the tick budget used, 1 being all of it.
+    pub budget_used: f64,
+}
+
+impl TickRecord {
+    pub fn new(username: &str, player_id: usize, tick: usize, alive: usize, strategy: &'static str) -> TickRecord {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        TickRecord {
+            time_ms: now.as_millis(),
+            username: username.to_string(),
+            player_id,
+            tick,
+            alive,
+            strategy,
+            direction: None,
+            fallback: None,
+            scores: BTreeMap::new(),
+            elapsed_ms: 0.0,
+            budget_used: 0.0,
+        }
+    }
+
+    pub fn set_scores(&mut self, scores: &[(Direction, f64)]) {
+        self.scores = scores.iter().map(|&(dir, score)| (dir.as_str(), score)).collect();
+    }
+}
+
+impl fmt::Display for TickRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Tick {}: {} alive, ", self.tick, self.alive)?;
+        match (self.direction, self.fallback) {
+            (Some(dir), None) => write!(f, "going {dir}")?,
+            (Some(dir), Some(fallback)) => write!(f, "sent fallback {fallback}, then decided on {dir}")?,
+            (None, Some(fallback)) => write!(f, "overran the budget, went with fallback {fallback}")?,
+            (None, None) => write!(f, "no move")?,
+        }
+        write!(f, " after {:.1} ms ({:.0}% of budget)", self.elapsed_ms, self.budget_used * 100.0)?;
+
+        if !self.scores.is_empty() {
+            let scores: Vec<_> = self.scores.iter().map(|(dir, score)| format!("{dir} {score:.2}")).collect();
+            write!(f, ", scores: {}", scores.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Appends [`TickRecord`]s to a file.
+pub struct Telemetry {
+    file: LineWriter<File>,
+    path: PathBuf,
+}
+
+impl Telemetry {
+    /// Opens `path` for appending, so several sessions can share one file.
+    pub fn open(path: &Path) -> io::Result<Telemetry> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Telemetry { file: LineWriter::new(file), path: path.to_path_buf() })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&mut self, record: &TickRecord) {
+        let result = serde_json::to_writer(&mut self.file, record).map_err(io::Error::from);
+        // Like the recording, telemetry is no reason to lose the round
+        if let Err(err) = result.and_then(|()| writeln!(self.file)) {
+            log::warn!("Failed to write to {}: {err}", self.path.display());
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::path::Path;
+
+use snek::config::Config;
+use snek::replay;
+use snek::strategy;
+
+/// A single player round recorded against snek-server with the `straight` strategy.
+const RECORDING: &str = "tests/data/straight-single-player.log";
+
+#[test]
+fn straight_makes_the_recorded_moves() {
+    let mut strategy = strategy::by_name("straight").unwrap();
+    let ticks = replay::run(Path::new(RECORDING), strategy.as_mut(), &Config::default()).unwrap();
+
+    assert_eq!(ticks.len(), 50);
+    let differing: Vec<_> = ticks.iter().filter(|t| t.differs()).collect();
+    assert!(differing.is_empty(), "moves differ: {differing:?}");
+}
+
+#[test]
+fn reports_moves_that_differ() {
+    let mut strategy = strategy::by_name("simple").unwrap();
+    let ticks = replay::run(Path::new(RECORDING), strategy.as_mut(), &Config::default()).unwrap();
+
+    assert!(ticks.iter().any(|t| t.differs()));
+    assert!(ticks.iter().all(|t| t.round == 1 && t.replayed.is_some()));
+}
+
+#[test]
+fn writes_a_telemetry_record_per_tick() {
+    let path = std::env::temp_dir().join(format!("snek-telemetry-{}.jsonl", std::process::id()));
+    let config = Config { telemetry: Some(path.clone()), ..Config::default() };
+    let mut strategy = strategy::by_name("territory").unwrap();
+    replay::run(Path::new(RECORDING), strategy.as_mut(), &config).unwrap();
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let records: Vec<serde_json::Value> = contents.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(records.len(), 50);
+    assert_eq!(records[0]["tick"], 1);
+    assert_eq!(records[0]["strategy"], "territory");
+    assert!(records.iter().all(|r| r["direction"].is_string() && r["scores"].is_object()));
+}